@@ -0,0 +1,170 @@
+#ifndef XALPS_H
+#define XALPS_H
+
+/* Generated by cbindgen from src/ffi.rs, do not edit. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+typedef enum XalpsAthleteStatus {
+  XALPS_ATHLETE_STATUS_REST,
+  XALPS_ATHLETE_STATUS_FLY,
+  XALPS_ATHLETE_STATUS_HIKE,
+} XalpsAthleteStatus;
+
+typedef enum XalpsErrorCode {
+  XALPS_ERROR_CODE_OK = 0,
+  // A required pointer argument was null.
+  XALPS_ERROR_CODE_NULL_ARGUMENT = 1,
+  // A string argument is not valid UTF-8 or not valid for its purpose.
+  XALPS_ERROR_CODE_INVALID_ARGUMENT = 2,
+  // The index is not below the item count.
+  XALPS_ERROR_CODE_INDEX_OUT_OF_RANGE = 3,
+  XALPS_ERROR_CODE_HTTP = 10,
+  // Upstream has no such data, e.g. no track of the athlete.
+  XALPS_ERROR_CODE_NOT_FOUND = 11,
+  XALPS_ERROR_CODE_JSON = 12,
+  XALPS_ERROR_CODE_PROTOBUF = 13,
+  XALPS_ERROR_CODE_NOT_RECORDED = 14,
+  XALPS_ERROR_CODE_IO = 15,
+  XALPS_ERROR_CODE_STORAGE = 16,
+  XALPS_ERROR_CODE_SERVER = 17,
+} XalpsErrorCode;
+
+typedef enum XalpsTrackKind {
+  XALPS_TRACK_KIND_LATEST,
+  XALPS_TRACK_KIND_REDUCED,
+} XalpsTrackKind;
+
+typedef struct XalpsClient XalpsClient;
+
+typedef struct XalpsOverview XalpsOverview;
+
+typedef struct XalpsRaceStatusList XalpsRaceStatusList;
+
+typedef struct XalpsTrack XalpsTrack;
+
+// Strings are owned by the `XalpsOverview` it was read from.
+typedef struct XalpsAthlete {
+  const char *athlete_id;
+  const char *firstname;
+  const char *lastname;
+  // ISO 3166-1 alpha-2.
+  const char *country_code;
+  const char *team;
+  bool hide;
+} XalpsAthlete;
+
+// `athlete_id` is owned by the `XalpsRaceStatusList` it was read from.
+typedef struct XalpsRaceStatus {
+  const char *athlete_id;
+  // Unix timestamp in seconds.
+  int64_t timestamp;
+  enum XalpsAthleteStatus status;
+  // Kilometres.
+  double distance_to_goal;
+  // Metres above sea level.
+  uint32_t altitude;
+} XalpsRaceStatus;
+
+typedef struct XalpsTrackPoint {
+  // Unix timestamp in seconds.
+  double timestamp;
+  float lat;
+  float lng;
+  float altitude;
+  float altitude_agl;
+  float speed;
+  float vertical_speed;
+} XalpsTrackPoint;
+
+// Message of the last error on this thread, null if there was none.
+// Valid until the next failing call on this thread.
+const char *xalps_last_error_message(void);
+
+// A client for the live feeds, or for `base_url` serving both hosts if not null.
+//
+// # Safety
+// `base_url` is null or a NUL-terminated string, `out` is valid for writes.
+enum XalpsErrorCode xalps_client_new(const char *base_url, struct XalpsClient **out);
+
+// # Safety
+// `client` is null or was returned by `xalps_client_new` and not freed yet.
+void xalps_client_free(struct XalpsClient *client);
+
+// # Safety
+// `client` is a live client, `out` is valid for writes.
+enum XalpsErrorCode xalps_overview_fetch(const struct XalpsClient *client,
+                                         struct XalpsOverview **out);
+
+// # Safety
+// `overview` is a live overview.
+size_t xalps_overview_athlete_count(const struct XalpsOverview *overview);
+
+// # Safety
+// `overview` is a live overview, `out` is valid for writes.
+enum XalpsErrorCode xalps_overview_athlete(const struct XalpsOverview *overview,
+                                           size_t index,
+                                           struct XalpsAthlete *out);
+
+// # Safety
+// `overview` is null or a live overview.
+void xalps_overview_free(struct XalpsOverview *overview);
+
+// # Safety
+// `client` is a live client, `out` is valid for writes.
+enum XalpsErrorCode xalps_race_status_fetch(const struct XalpsClient *client,
+                                            struct XalpsRaceStatusList **out);
+
+// # Safety
+// `list` is a live race status list.
+size_t xalps_race_status_count(const struct XalpsRaceStatusList *list);
+
+// # Safety
+// `list` is a live race status list, `out` is valid for writes.
+enum XalpsErrorCode xalps_race_status_get(const struct XalpsRaceStatusList *list,
+                                          size_t index,
+                                          struct XalpsRaceStatus *out);
+
+// # Safety
+// `list` is null or a live race status list.
+void xalps_race_status_free(struct XalpsRaceStatusList *list);
+
+// # Safety
+// `client` is a live client, `athlete_id` a NUL-terminated string, `out` is valid for writes.
+enum XalpsErrorCode xalps_track_fetch(const struct XalpsClient *client,
+                                      const char *athlete_id,
+                                      enum XalpsTrackKind kind,
+                                      struct XalpsTrack **out);
+
+// The track as it was at `time`, in Unix seconds on a 5 minute boundary.
+//
+// # Safety
+// `client` is a live client, `athlete_id` a NUL-terminated string, `out` is valid for writes.
+enum XalpsErrorCode xalps_track_fetch_replay(const struct XalpsClient *client,
+                                             const char *athlete_id,
+                                             int64_t time,
+                                             struct XalpsTrack **out);
+
+// # Safety
+// `track` is a live track.
+int32_t xalps_track_athlete_id(const struct XalpsTrack *track);
+
+// # Safety
+// `track` is a live track.
+size_t xalps_track_point_count(const struct XalpsTrack *track);
+
+// # Safety
+// `track` is a live track, `out` is valid for writes.
+enum XalpsErrorCode xalps_track_point(const struct XalpsTrack *track,
+                                      size_t index,
+                                      struct XalpsTrackPoint *out);
+
+// # Safety
+// `track` is null or a live track.
+void xalps_track_free(struct XalpsTrack *track);
+
+#endif /* XALPS_H */
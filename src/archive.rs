@@ -0,0 +1,580 @@
+//! Continuous snapshots of the whole race into a [`Storage`].
+//!
+//! On start the overview is fetched once and everything missed since the
+//! newest stored data is backfilled from the replay feeds, then the race status
+//! and every athlete's latest track are polled until the process is stopped.
+
+use crate::{
+    storage::Storage, ApiTrackResponse, AthleteId, Client, DecodeReport, Error, Overview,
+    RaceStatus, RaceStatusReplay, Repair, Track,
+};
+use chrono::{
+    DateTime, Duration as ChronoDuration, DurationRound, Local, NaiveDate, TimeZone, Utc,
+};
+use std::{fmt, time::Duration};
+
+/// Resolution of the `latest-replay` track feed.
+const REPLAY_STEP: Duration = Duration::from_secs(5 * 60);
+
+#[derive(Debug, Clone)]
+pub struct ArchiveConfig {
+    /// How often the race status is fetched.
+    pub status_interval: Duration,
+    /// How often every athlete's latest track is fetched.
+    pub track_interval: Duration,
+    /// Consecutive track points further apart than this are reported as a coverage gap.
+    pub gap_threshold: Duration,
+    /// How much time after the newest stored data a restart backfills at most.
+    pub max_backfill: Duration,
+}
+
+impl Default for ArchiveConfig {
+    fn default() -> Self {
+        ArchiveConfig {
+            status_interval: Duration::from_secs(60),
+            track_interval: Duration::from_secs(5 * 60),
+            gap_threshold: Duration::from_secs(10 * 60),
+            max_backfill: Duration::from_secs(48 * 60 * 60),
+        }
+    }
+}
+
+impl ArchiveConfig {
+    /// Fails if an interval is zero.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.status_interval.is_zero() {
+            return Err(Error::InvalidConfig(
+                "status interval must not be zero".to_string(),
+            ));
+        }
+        if self.track_interval.is_zero() {
+            return Err(Error::InvalidConfig(
+                "track interval must not be zero".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// What the archiver did or failed to do, see [`Archiver::with_observer`].
+#[derive(Debug)]
+pub enum ArchiveEvent {
+    /// The overview was stored and these many athletes are archived.
+    Started {
+        athletes: usize,
+    },
+    /// A race status snapshot of these many athletes was stored.
+    StatusStored {
+        athletes: usize,
+    },
+    StatusFailed(Error),
+    /// New points of an athlete's track were stored.
+    TrackStored {
+        athlete_id: AthleteId,
+        points: usize,
+    },
+    TrackFailed {
+        athlete_id: AthleteId,
+        error: Error,
+    },
+    /// An athlete's track didn't match the feed schema, what could be decoded
+    /// was stored, see [`Track::decode_lenient`].
+    TrackRepaired {
+        athlete_id: AthleteId,
+        report: DecodeReport,
+    },
+    TrackBackfillFailed {
+        athlete_id: AthleteId,
+        at: DateTime<Utc>,
+        error: Error,
+    },
+    StatusBackfillFailed {
+        day: NaiveDate,
+        error: Error,
+    },
+    /// The newest stored timestamp of an athlete's track, or of the race status
+    /// if `None`, is out of range, so nothing is backfilled after it.
+    InvalidTimestamp {
+        athlete_id: Option<AthleteId>,
+        timestamp: f64,
+    },
+    /// No track points of an athlete between `from` and `to`, longer than the gap threshold.
+    CoverageGap {
+        athlete_id: AthleteId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    },
+}
+
+impl ArchiveEvent {
+    /// Whether the event reports a failure or missing data.
+    pub fn is_error(&self) -> bool {
+        !matches!(
+            self,
+            ArchiveEvent::Started { .. }
+                | ArchiveEvent::StatusStored { .. }
+                | ArchiveEvent::TrackStored { .. }
+        )
+    }
+}
+
+impl fmt::Display for ArchiveEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ArchiveEvent::Started { athletes } => {
+                write!(f, "archiving {} athletes into the local store", athletes)
+            }
+            ArchiveEvent::StatusStored { athletes } => {
+                write!(f, "stored race status of {} athletes", athletes)
+            }
+            ArchiveEvent::StatusFailed(err) => write!(f, "failed to archive race status: {}", err),
+            ArchiveEvent::TrackStored { athlete_id, points } => write!(
+                f,
+                "stored {} new track points of athlete {}",
+                points, athlete_id
+            ),
+            ArchiveEvent::TrackFailed { athlete_id, error } => write!(
+                f,
+                "failed to archive track of athlete {}: {}",
+                athlete_id, error
+            ),
+            ArchiveEvent::TrackRepaired { athlete_id, report } => {
+                let dropped = report
+                    .points
+                    .iter()
+                    .filter(|issue| issue.repair == Repair::Dropped)
+                    .count();
+                write!(
+                    f,
+                    "track of athlete {} doesn't match the schema: {} points dropped, \
+                     {} incomplete, {} unknown fields",
+                    athlete_id,
+                    dropped,
+                    report.points.len() - dropped,
+                    report.unknown_fields.len()
+                )
+            }
+            ArchiveEvent::TrackBackfillFailed {
+                athlete_id,
+                at,
+                error,
+            } => write!(
+                f,
+                "failed to backfill track of athlete {} at {}: {}",
+                athlete_id,
+                at.to_rfc3339(),
+                error
+            ),
+            ArchiveEvent::StatusBackfillFailed { day, error } => write!(
+                f,
+                "failed to backfill race status of {}: {}",
+                day.format("%Y-%m-%d"),
+                error
+            ),
+            ArchiveEvent::InvalidTimestamp {
+                athlete_id: Some(athlete_id),
+                timestamp,
+            } => write!(
+                f,
+                "can't backfill track of athlete {} after out of range timestamp {}",
+                athlete_id, timestamp
+            ),
+            ArchiveEvent::InvalidTimestamp {
+                athlete_id: None,
+                timestamp,
+            } => write!(
+                f,
+                "can't backfill race status after out of range timestamp {}",
+                timestamp
+            ),
+            ArchiveEvent::CoverageGap {
+                athlete_id,
+                from,
+                to,
+            } => write!(
+                f,
+                "coverage gap for athlete {}: no track points from {} to {}",
+                athlete_id,
+                from.to_rfc3339(),
+                to.to_rfc3339()
+            ),
+        }
+    }
+}
+
+type Observer = Box<dyn Fn(&ArchiveEvent) + Send + Sync>;
+
+pub struct Archiver {
+    client: Client,
+    storage: Storage,
+    config: ArchiveConfig,
+    observer: Option<Observer>,
+    athlete_ids: Vec<AthleteId>,
+    race_end: Option<DateTime<Utc>>,
+}
+
+impl Archiver {
+    /// Fails if the config is [invalid](ArchiveConfig::validate).
+    pub fn new(client: Client, storage: Storage, config: ArchiveConfig) -> Result<Archiver, Error> {
+        config.validate()?;
+        Ok(Archiver {
+            client,
+            storage,
+            config,
+            observer: None,
+            athlete_ids: Vec::new(),
+            race_end: None,
+        })
+    }
+
+    /// Calls `observer` with everything stored and every failure, which are
+    /// otherwise skipped silently while running.
+    pub fn with_observer(
+        mut self,
+        observer: impl Fn(&ArchiveEvent) + Send + Sync + 'static,
+    ) -> Archiver {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    fn emit(&self, event: ArchiveEvent) {
+        if let Some(observer) = &self.observer {
+            observer(&event);
+        }
+    }
+
+    pub fn storage(&self) -> &Storage {
+        &self.storage
+    }
+
+    /// Starts and polls forever; only returns if the overview can't be fetched.
+    pub async fn run(&mut self) -> Result<(), Error> {
+        self.start().await?;
+        let mut status = tokio::time::interval(self.config.status_interval);
+        let mut tracks = tokio::time::interval(self.config.track_interval);
+        loop {
+            tokio::select! {
+                _ = status.tick() => match self.snapshot_status().await {
+                    Ok(athletes) => self.emit(ArchiveEvent::StatusStored { athletes }),
+                    Err(err) => self.emit(ArchiveEvent::StatusFailed(err)),
+                },
+                _ = tracks.tick() => {
+                    self.snapshot_tracks().await;
+                }
+            }
+        }
+    }
+
+    /// Stores the overview and backfills what was missed since the last run.
+    pub async fn start(&mut self) -> Result<(), Error> {
+        let overview = Overview::request_with(&self.client).await?;
+        self.storage.store_overview(&overview, Utc::now())?;
+        self.athlete_ids = overview
+            .athletes
+            .iter()
+            .map(|athlete| athlete.athlete_id)
+            .collect();
+        self.race_end = Utc
+            .timestamp_opt(overview.race_dates.end_time as i64, 0)
+            .single();
+        self.emit(ArchiveEvent::Started {
+            athletes: self.athlete_ids.len(),
+        });
+        self.resume().await
+    }
+
+    /// Stores the race status. Returns the number of athletes in it.
+    pub async fn snapshot_status(&self) -> Result<usize, Error> {
+        let status = RaceStatus::request_with(&self.client).await?;
+        self.storage.store_race_status(&status)?;
+        Ok(status.len())
+    }
+
+    /// Fetches and stores the latest track of every athlete, reporting failures
+    /// to the observer. Returns the number of new points.
+    pub async fn snapshot_tracks(&self) -> usize {
+        let mut total = 0;
+        for &athlete_id in &self.athlete_ids {
+            let stored =
+                match ApiTrackResponse::request_lenient_with(&self.client, athlete_id).await {
+                    Ok(decoded) => self.store_track(&self.repaired(athlete_id, decoded)),
+                    Err(err) => Err(err),
+                };
+            match stored {
+                Ok(0) => {}
+                Ok(points) => {
+                    total += points;
+                    self.emit(ArchiveEvent::TrackStored { athlete_id, points });
+                }
+                Err(error) => self.emit(ArchiveEvent::TrackFailed { athlete_id, error }),
+            }
+        }
+        total
+    }
+
+    /// Backfills tracks and race status from the replay feeds, starting at the
+    /// newest stored data, until now, the end of the race or `max_backfill`.
+    async fn resume(&self) -> Result<(), Error> {
+        let now = self.race_end.map_or(Utc::now(), |end| end.min(Utc::now()));
+        let max_backfill = ChronoDuration::from_std(self.config.max_backfill)
+            .unwrap_or_else(|_| ChronoDuration::max_value());
+        let step = ChronoDuration::from_std(REPLAY_STEP).unwrap();
+
+        for &athlete_id in &self.athlete_ids {
+            let Some(last) = self.storage.latest_track_timestamp(athlete_id)? else {
+                continue;
+            };
+            let Some((last, start)) = Utc
+                .timestamp_millis_opt((last * 1000.0) as i64)
+                .single()
+                .and_then(|last| Some((last, last.duration_trunc(step).ok()?)))
+            else {
+                self.emit(ArchiveEvent::InvalidTimestamp {
+                    athlete_id: Some(athlete_id),
+                    timestamp: last,
+                });
+                continue;
+            };
+            let until = last
+                .checked_add_signed(max_backfill)
+                .map_or(now, |end| end.min(now));
+            let mut at = start + step;
+            while at <= until {
+                match ApiTrackResponse::request_replay_lenient_with(&self.client, athlete_id, at)
+                    .await
+                {
+                    Ok(decoded) => {
+                        self.store_track(&self.repaired(athlete_id, decoded))?;
+                    }
+                    Err(error) => self.emit(ArchiveEvent::TrackBackfillFailed {
+                        athlete_id,
+                        at,
+                        error,
+                    }),
+                }
+                at += step;
+            }
+        }
+
+        if let Some(timestamp) = self.storage.latest_status_timestamp()? {
+            let Some(last) = Utc.timestamp_opt(timestamp, 0).single() else {
+                self.emit(ArchiveEvent::InvalidTimestamp {
+                    athlete_id: None,
+                    timestamp: timestamp as f64,
+                });
+                return Ok(());
+            };
+            let until = last
+                .checked_add_signed(max_backfill)
+                .map_or(now, |end| end.min(now));
+            // Replays are per local day, like `RaceStatusReplay::request`.
+            let until = until.with_timezone(&Local).date_naive();
+            let mut day = last.with_timezone(&Local).date_naive();
+            while day <= until {
+                // Noon exists on every day, unlike midnight with some DST changes.
+                if let Some(local) = day
+                    .and_hms_opt(12, 0, 0)
+                    .and_then(|noon| noon.and_local_timezone(Local).earliest())
+                {
+                    match RaceStatusReplay::request_with(&self.client, local).await {
+                        Ok(replay) => self.storage.store_replay(&replay)?,
+                        Err(error) => self.emit(ArchiveEvent::StatusBackfillFailed { day, error }),
+                    }
+                }
+                let Some(next) = day.succ_opt() else {
+                    break;
+                };
+                day = next;
+            }
+        }
+        Ok(())
+    }
+
+    /// Stores the track, reporting gaps in the coverage. Returns the number of new points.
+    /// Reports what lenient decoding repaired and attributes a track without
+    /// `athlete_id` to the athlete it was requested for.
+    fn repaired(&self, athlete_id: AthleteId, (mut track, report): (Track, DecodeReport)) -> Track {
+        if report.missing_athlete_id {
+            track.athlete_id = athlete_id;
+        }
+        if !report.is_clean() {
+            self.emit(ArchiveEvent::TrackRepaired { athlete_id, report });
+        }
+        track
+    }
+
+    fn store_track(&self, track: &Track) -> Result<usize, Error> {
+        let gap_threshold = self.config.gap_threshold.as_secs_f64();
+        let mut last = self.storage.latest_track_timestamp(track.athlete_id)?;
+        let mut new = 0;
+        for point in &track.points {
+            if last.is_some_and(|last| point.timestamp <= last) {
+                continue;
+            }
+            if let Some(last) = last.filter(|last| point.timestamp - last > gap_threshold) {
+                self.emit(ArchiveEvent::CoverageGap {
+                    athlete_id: track.athlete_id,
+                    from: Utc
+                        .timestamp_millis_opt((last * 1000.0) as i64)
+                        .single()
+                        .unwrap_or_default(),
+                    to: point.time(),
+                });
+            }
+            last = Some(point.timestamp);
+            new += 1;
+        }
+        self.storage.store_track(track)?;
+        Ok(new)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{ArchiveConfig, Archiver};
+    use crate::{
+        storage::Storage,
+        test_util::{self, MockServer},
+        ApiTrackResponse, AthleteId, Track,
+    };
+    use chrono::{DateTime, Utc};
+    use std::{
+        sync::{Arc, Mutex},
+        time::Duration,
+    };
+
+    fn time(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().into()
+    }
+
+    #[tokio::test]
+    async fn snapshot() {
+        let server = MockServer::start().await;
+        let mut archiver = Archiver::new(
+            server.client(),
+            Storage::open_in_memory().unwrap(),
+            ArchiveConfig::default(),
+        )
+        .unwrap();
+        archiver.start().await.unwrap();
+        archiver.snapshot_status().await.unwrap();
+        archiver.snapshot_tracks().await;
+
+        let storage = archiver.storage();
+        assert!(storage.overview().unwrap().is_some());
+        assert_eq!(storage.race_status_at(Utc::now()).unwrap().len(), 3);
+        let track = storage
+            .track(
+                AthleteId::new(29),
+                time("2023-06-14T00:00:00Z"),
+                time("2023-06-15T00:00:00Z"),
+            )
+            .unwrap();
+        assert_eq!(track.points.len(), 60);
+    }
+
+    #[tokio::test]
+    async fn incomplete_track_is_stored() {
+        let server = MockServer::start().await;
+        server.route(
+            "/race/athlete/29/track/latest.pbf",
+            test_util::incomplete_track(),
+        );
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut archiver = Archiver::new(
+            server.client(),
+            Storage::open_in_memory().unwrap(),
+            ArchiveConfig::default(),
+        )
+        .unwrap()
+        .with_observer({
+            let events = events.clone();
+            move |event| events.lock().unwrap().push(event.to_string())
+        });
+        archiver.start().await.unwrap();
+        assert_eq!(archiver.snapshot_tracks().await, 60);
+
+        let events = events.lock().unwrap();
+        assert!(events.contains(
+            &"track of athlete 29 doesn't match the schema: 0 points dropped, 1 incomplete, \
+              0 unknown fields"
+                .to_string()
+        ));
+    }
+
+    #[test]
+    fn zero_interval() {
+        let config = ArchiveConfig {
+            track_interval: Duration::ZERO,
+            ..ArchiveConfig::default()
+        };
+        let archiver = Archiver::new(
+            crate::Client::shared().clone(),
+            Storage::open_in_memory().unwrap(),
+            config,
+        );
+        assert!(matches!(archiver, Err(crate::Error::InvalidConfig(_))));
+    }
+
+    #[tokio::test]
+    async fn invalid_timestamp_is_reported() {
+        let server = MockServer::start().await;
+        let response = ApiTrackResponse::request_with(&server.client(), AthleteId::new(29))
+            .await
+            .unwrap();
+        let mut track = Track::from(response);
+        track.points.truncate(1);
+        track.points[0].timestamp = 1e18;
+
+        let storage = Storage::open_in_memory().unwrap();
+        storage.store_track(&track).unwrap();
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let mut archiver = Archiver::new(server.client(), storage, ArchiveConfig::default())
+            .unwrap()
+            .with_observer({
+                let events = events.clone();
+                move |event| events.lock().unwrap().push(event.to_string())
+            });
+        archiver.start().await.unwrap();
+
+        let events = events.lock().unwrap();
+        assert!(events
+            .iter()
+            .any(|event| event.contains("out of range timestamp")));
+    }
+
+    #[tokio::test]
+    async fn resume_backfills_from_replay() {
+        let server = MockServer::start().await;
+        let response = ApiTrackResponse::request_with(&server.client(), AthleteId::new(29))
+            .await
+            .unwrap();
+        let mut track = Track::from(response);
+        track.points.truncate(46);
+
+        let storage = Storage::open_in_memory().unwrap();
+        storage.store_track(&track).unwrap();
+        let mut archiver = Archiver::new(
+            server.client(),
+            storage,
+            ArchiveConfig {
+                max_backfill: Duration::from_secs(5 * 60),
+                ..ArchiveConfig::default()
+            },
+        )
+        .unwrap();
+        archiver.start().await.unwrap();
+
+        assert_eq!(
+            server.hits("/race/athlete/29/track/latest-replay/2023-06-14T16:50:00Z.pbf"),
+            1
+        );
+        assert_eq!(
+            archiver
+                .storage()
+                .latest_track_timestamp(AthleteId::new(29))
+                .unwrap(),
+            Some(time("2023-06-14T16:50:00Z").timestamp() as f64)
+        );
+    }
+}
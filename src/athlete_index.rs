@@ -0,0 +1,259 @@
+//! Lookups of athletes by id, slug, name, country or team, see [`Overview::athlete_index`].
+
+use crate::{Athlete, AthleteId, Overview};
+use deunicode::deunicode;
+use std::{collections::HashMap, fmt};
+
+/// Lookups of the athletes of an [`Overview`] by id, `lt_slug`, name, country or team.
+///
+/// Names are compared case and diacritic insensitively, so `kanel` and
+/// `känel` both find Patrick von Känel; `kaenel` finds him by his slug.
+#[derive(Debug, Clone)]
+pub struct AthleteIndex<'a> {
+    athletes: &'a [Athlete],
+    by_id: HashMap<AthleteId, usize>,
+    by_slug: HashMap<String, usize>,
+    /// Normalised words of the full name and of the slug of every athlete.
+    names: Vec<(Vec<String>, Vec<String>)>,
+}
+
+/// Why [`AthleteIndex::find`] found no single athlete.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FindError {
+    NotFound(String),
+    /// The query matches the full names of several athletes.
+    Ambiguous {
+        query: String,
+        matches: Vec<String>,
+    },
+}
+
+impl fmt::Display for FindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindError::NotFound(query) => write!(f, "no athlete matches {}", query),
+            FindError::Ambiguous { query, matches } => write!(
+                f,
+                "{} matches several athletes: {}",
+                query,
+                matches.join(", ")
+            ),
+        }
+    }
+}
+
+impl std::error::Error for FindError {}
+
+/// Lowercase ASCII words, e.g. `["patrick", "von", "kanel"]` for `Patrick von Känel`.
+fn words(text: &str) -> Vec<String> {
+    deunicode(text)
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// How well a query matches a name, best first.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Match {
+    /// Every word, in order.
+    Exact,
+    /// Every query word starts a word of the name.
+    Prefix,
+    /// The query is part of the name.
+    Substring,
+}
+
+fn name_match(query: &[String], name: &[String]) -> Option<Match> {
+    if query.is_empty() {
+        return None;
+    }
+    if query == name {
+        return Some(Match::Exact);
+    }
+    if query.iter().all(|word| {
+        name.iter()
+            .any(|name_word| name_word.starts_with(word.as_str()))
+    }) {
+        return Some(Match::Prefix);
+    }
+    name.join(" ")
+        .contains(&query.join(" "))
+        .then_some(Match::Substring)
+}
+
+impl<'a> AthleteIndex<'a> {
+    pub fn new(overview: &'a Overview) -> AthleteIndex<'a> {
+        let athletes = overview.athletes.as_slice();
+        AthleteIndex {
+            athletes,
+            by_id: athletes
+                .iter()
+                .enumerate()
+                .map(|(index, athlete)| (athlete.athlete_id, index))
+                .collect(),
+            by_slug: athletes
+                .iter()
+                .enumerate()
+                .map(|(index, athlete)| (athlete.lt_slug.to_lowercase(), index))
+                .collect(),
+            names: athletes
+                .iter()
+                .map(|athlete| (words(&athlete.full_name()), words(&athlete.lt_slug)))
+                .collect(),
+        }
+    }
+
+    pub fn athletes(&self) -> &'a [Athlete] {
+        self.athletes
+    }
+
+    pub fn by_id(&self, athlete_id: AthleteId) -> Option<&'a Athlete> {
+        self.by_id
+            .get(&athlete_id)
+            .map(|&index| &self.athletes[index])
+    }
+
+    /// The athlete by `lt_slug`, ignoring case.
+    pub fn by_slug(&self, slug: &str) -> Option<&'a Athlete> {
+        self.by_slug
+            .get(&slug.to_lowercase())
+            .map(|&index| &self.athletes[index])
+    }
+
+    /// Athletes whose name matches `query`, the best matches first: the
+    /// full name, then names with words starting with every query word,
+    /// then names containing the query.
+    pub fn search(&self, query: &str) -> Vec<&'a Athlete> {
+        let mut matches = self.ranked(query);
+        matches.sort_by_key(|(rank, _)| *rank);
+        matches.into_iter().map(|(_, athlete)| athlete).collect()
+    }
+
+    fn ranked(&self, query: &str) -> Vec<(Match, &'a Athlete)> {
+        let query = words(query);
+        self.athletes
+            .iter()
+            .zip(&self.names)
+            .filter_map(|(athlete, (name, slug))| {
+                let rank = [name_match(&query, name), name_match(&query, slug)]
+                    .into_iter()
+                    .flatten()
+                    .min()?;
+                Some((rank, athlete))
+            })
+            .collect()
+    }
+
+    /// Athletes of a country, by ISO 3166-1 alpha-2 or alpha-3 code.
+    pub fn by_country(&self, code: &str) -> Vec<&'a Athlete> {
+        self.athletes
+            .iter()
+            .filter(|athlete| {
+                athlete.country_code.alpha2().eq_ignore_ascii_case(code)
+                    || athlete.country_code.alpha3().eq_ignore_ascii_case(code)
+            })
+            .collect()
+    }
+
+    /// Athletes of a team, e.g. `SUI1`, ignoring case.
+    pub fn by_team(&self, team: &str) -> Vec<&'a Athlete> {
+        self.athletes
+            .iter()
+            .filter(|athlete| athlete.team.eq_ignore_ascii_case(team))
+            .collect()
+    }
+
+    /// The athlete by id, `lt_slug` or the one best name match.
+    pub fn find(&self, query: &str) -> Result<&'a Athlete, FindError> {
+        let by_id = query
+            .parse::<AthleteId>()
+            .ok()
+            .and_then(|id| self.by_id(id));
+        if let Some(athlete) = by_id.or_else(|| self.by_slug(query)) {
+            return Ok(athlete);
+        }
+        let ranked = self.ranked(query);
+        let Some(best) = ranked.iter().map(|(rank, _)| *rank).min() else {
+            return Err(FindError::NotFound(query.to_string()));
+        };
+        let best: Vec<&Athlete> = ranked
+            .into_iter()
+            .filter(|(rank, _)| *rank == best)
+            .map(|(_, athlete)| athlete)
+            .collect();
+        match best.as_slice() {
+            [athlete] => Ok(athlete),
+            matches => Err(FindError::Ambiguous {
+                query: query.to_string(),
+                matches: matches.iter().map(|athlete| athlete.full_name()).collect(),
+            }),
+        }
+    }
+}
+
+impl Overview {
+    pub fn athlete_index(&self) -> AthleteIndex<'_> {
+        AthleteIndex::new(self)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AthleteIndex, FindError};
+    use crate::{test_util, Athlete, AthleteId, Overview};
+
+    fn overview() -> Overview {
+        serde_json::from_slice(test_util::fixture(test_util::OVERVIEW_PATH).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn lookups() {
+        let overview = overview();
+        let index = AthleteIndex::new(&overview);
+        fn ids(athletes: Vec<&Athlete>) -> Vec<i32> {
+            athletes
+                .into_iter()
+                .map(|athlete| athlete.athlete_id.get())
+                .collect()
+        }
+        assert_eq!(index.by_id(AthleteId::new(29)).unwrap().lastname, "Maurer");
+        assert!(index.by_id(AthleteId::new(1)).is_none());
+        assert_eq!(
+            index.by_slug("Maxime-Pinot").unwrap().athlete_id,
+            AthleteId::new(12)
+        );
+        assert_eq!(ids(index.by_country("ch")), [29, 3]);
+        assert_eq!(ids(index.by_country("FRA")), [12]);
+        assert_eq!(ids(index.by_team("sui2")), [3]);
+
+        assert_eq!(ids(index.search("Känel")), [3]);
+        assert_eq!(ids(index.search("KANEL")), [3]);
+        assert_eq!(ids(index.search("kaenel")), [3]);
+        assert_eq!(ids(index.search("p v kan")), [3]);
+        assert_eq!(ids(index.search("ma")), [29, 12]);
+        assert!(index.search("  ").is_empty());
+    }
+
+    #[test]
+    fn find() {
+        let overview = overview();
+        let index = overview.athlete_index();
+        let id = |query| index.find(query).map(|athlete| athlete.athlete_id.get());
+        assert_eq!(id("29"), Ok(29));
+        assert_eq!(id("patrick-von-kaenel"), Ok(3));
+        assert_eq!(id("maxime pinot"), Ok(12));
+        assert_eq!(id("maurer"), Ok(29));
+        assert_eq!(id("von känel"), Ok(3));
+        assert_eq!(id("aurer"), Ok(29));
+        assert_eq!(
+            id("ma"),
+            Err(FindError::Ambiguous {
+                query: "ma".to_string(),
+                matches: vec!["Christian Maurer".to_string(), "Maxime Pinot".to_string()],
+            })
+        );
+        assert_eq!(id("nobody"), Err(FindError::NotFound("nobody".to_string())));
+    }
+}
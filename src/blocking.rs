@@ -0,0 +1,180 @@
+//! Synchronous counterparts of every `request*` function, for scripts and
+//! tools that don't want an async runtime of their own.
+//!
+//! `Overview::request()` becomes `Overview::request_blocking()`, and
+//! `Overview::request_with(&client)` becomes
+//! `Overview::request_blocking_with(&client)` with a [`blocking::Client`](Client).
+//! They drive the async request on a runtime owned by the client, so they
+//! panic if called from within an async runtime.
+
+use crate::{ApiTrackResponse, AthleteId, Error, Overview, RaceStatus, RaceStatusReplay};
+use chrono::{DateTime, Local, Utc};
+use std::{future::Future, sync::OnceLock};
+use tokio::runtime::{Builder, Runtime};
+
+/// A [`crate::Client`] with the runtime its requests are driven on.
+#[derive(Debug)]
+pub struct Client {
+    client: crate::Client,
+    runtime: Runtime,
+}
+
+impl Client {
+    /// Wraps `client`, e.g. one configured with [`crate::Client::builder`].
+    pub fn new(client: crate::Client) -> Result<Client, Error> {
+        Ok(Client {
+            client,
+            runtime: Builder::new_current_thread().enable_all().build()?,
+        })
+    }
+
+    /// Client used by the `request_blocking*` functions that don't take one.
+    pub fn shared() -> &'static Client {
+        static SHARED: OnceLock<Client> = OnceLock::new();
+        SHARED.get_or_init(|| {
+            Client::new(crate::Client::default())
+                .expect("failed to initialise the blocking runtime")
+        })
+    }
+
+    pub fn client(&self) -> &crate::Client {
+        &self.client
+    }
+
+    fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+impl Overview {
+    pub fn request_blocking() -> Result<Overview, Error> {
+        Overview::request_blocking_with(Client::shared())
+    }
+
+    pub fn request_blocking_with(client: &Client) -> Result<Overview, Error> {
+        client.block_on(Overview::request_with(&client.client))
+    }
+}
+
+impl RaceStatus {
+    pub fn request_blocking() -> Result<Vec<RaceStatus>, Error> {
+        RaceStatus::request_blocking_with(Client::shared())
+    }
+
+    pub fn request_blocking_with(client: &Client) -> Result<Vec<RaceStatus>, Error> {
+        client.block_on(RaceStatus::request_with(&client.client))
+    }
+}
+
+impl RaceStatusReplay {
+    pub fn request_blocking(date: DateTime<Local>) -> Result<Vec<RaceStatusReplay>, Error> {
+        RaceStatusReplay::request_blocking_with(Client::shared(), date)
+    }
+
+    pub fn request_blocking_with(
+        client: &Client,
+        date: DateTime<Local>,
+    ) -> Result<Vec<RaceStatusReplay>, Error> {
+        client.block_on(RaceStatusReplay::request_with(&client.client, date))
+    }
+
+    pub fn request_today_blocking() -> Result<Vec<RaceStatusReplay>, Error> {
+        RaceStatusReplay::request_today_blocking_with(Client::shared())
+    }
+
+    pub fn request_today_blocking_with(client: &Client) -> Result<Vec<RaceStatusReplay>, Error> {
+        client.block_on(RaceStatusReplay::request_today_with(&client.client))
+    }
+}
+
+impl ApiTrackResponse {
+    pub fn request_blocking(athlete_id: AthleteId) -> Result<ApiTrackResponse, Error> {
+        ApiTrackResponse::request_blocking_with(Client::shared(), athlete_id)
+    }
+
+    pub fn request_blocking_with(
+        client: &Client,
+        athlete_id: AthleteId,
+    ) -> Result<ApiTrackResponse, Error> {
+        client.block_on(ApiTrackResponse::request_with(&client.client, athlete_id))
+    }
+
+    /// # Only 5 Minute gaps allowed
+    pub fn request_replay_blocking(
+        athlete_id: AthleteId,
+        date_time: DateTime<Utc>,
+    ) -> Result<ApiTrackResponse, Error> {
+        ApiTrackResponse::request_replay_blocking_with(Client::shared(), athlete_id, date_time)
+    }
+
+    /// # Only 5 Minute gaps allowed
+    pub fn request_replay_blocking_with(
+        client: &Client,
+        athlete_id: AthleteId,
+        date_time: DateTime<Utc>,
+    ) -> Result<ApiTrackResponse, Error> {
+        client.block_on(ApiTrackResponse::request_replay_with(
+            &client.client,
+            athlete_id,
+            date_time,
+        ))
+    }
+
+    pub fn request_reduced_blocking(athlete_id: AthleteId) -> Result<ApiTrackResponse, Error> {
+        ApiTrackResponse::request_reduced_blocking_with(Client::shared(), athlete_id)
+    }
+
+    pub fn request_reduced_blocking_with(
+        client: &Client,
+        athlete_id: AthleteId,
+    ) -> Result<ApiTrackResponse, Error> {
+        client.block_on(ApiTrackResponse::request_reduced_with(
+            &client.client,
+            athlete_id,
+        ))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Client;
+    use crate::{
+        test_util::MockServer, ApiTrackResponse, AthleteId, Overview, RaceStatus, RaceStatusReplay,
+    };
+    use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+
+    #[test]
+    fn requests_without_a_runtime() {
+        // The mock server needs a runtime, the blocking client brings its own.
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(MockServer::start());
+        let client = Client::new(server.client()).unwrap();
+
+        let overview = Overview::request_blocking_with(&client).unwrap();
+        assert!(!overview.athletes.is_empty());
+        assert_eq!(RaceStatus::request_blocking_with(&client).unwrap().len(), 3);
+        let day = Local
+            .from_local_datetime(
+                &NaiveDate::from_ymd_opt(2023, 6, 14)
+                    .unwrap()
+                    .and_hms_opt(12, 0, 0)
+                    .unwrap(),
+            )
+            .unwrap();
+        assert!(!RaceStatusReplay::request_blocking_with(&client, day)
+            .unwrap()
+            .is_empty());
+
+        let latest = ApiTrackResponse::request_blocking_with(&client, AthleteId::new(29)).unwrap();
+        assert_eq!(latest.get_track_points().len(), 60);
+        let reduced =
+            ApiTrackResponse::request_reduced_blocking_with(&client, AthleteId::new(29)).unwrap();
+        assert_eq!(reduced.get_track_points().len(), 12);
+        let at: DateTime<Utc> = "2023-06-14T16:50:00Z".parse().unwrap();
+        let replay =
+            ApiTrackResponse::request_replay_blocking_with(&client, AthleteId::new(29), at)
+                .unwrap();
+        assert_eq!(replay.get_track_points().len(), 51);
+        assert!(ApiTrackResponse::request_blocking_with(&client, AthleteId::new(3)).is_err());
+    }
+}
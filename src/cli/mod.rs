@@ -0,0 +1,259 @@
+//! Subcommands of the `xalps` binary that print feeds for humans or, with `--json`, for scripts.
+
+mod table;
+#[cfg(feature = "tui")]
+pub mod tui;
+
+use chrono::{DateTime, Duration, DurationRound, Local, NaiveDate, TimeZone, Utc};
+use serde::Serialize;
+use std::{error::Error, fs::File, io::Write, path::Path};
+use table::Table;
+use xalps::{
+    export::Format, ApiTrackResponse, Athlete, Overview, RaceStatus, RaceStatusReplay, Track,
+};
+
+type Result<T> = std::result::Result<T, Box<dyn Error>>;
+
+/// Which of the track feeds to read.
+pub enum TrackSource {
+    Latest,
+    Reduced,
+    /// Replay at a time, rounded down to the 5 minute steps the feed supports.
+    At(DateTime<Utc>),
+}
+
+fn print_json(value: &impl Serialize) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(value)?);
+    Ok(())
+}
+
+/// Time since `timestamp` (Unix seconds) in the largest fitting units.
+fn age(timestamp: i64, now: DateTime<Utc>) -> String {
+    let seconds = (now.timestamp() - timestamp).max(0);
+    match seconds {
+        0..=59 => format!("{}s", seconds),
+        60..=3599 => format!("{}m", seconds / 60),
+        3600..=86399 => format!("{}h {}m", seconds / 3600, seconds % 3600 / 60),
+        _ => format!("{}d {}h", seconds / 86400, seconds % 86400 / 3600),
+    }
+}
+
+pub async fn overview(json: bool) -> Result<()> {
+    let overview = Overview::request().await?;
+    if json {
+        return print_json(&overview);
+    }
+    let mut athletes = Table::new(&["ID", "Name", "Country", "Team", "Slug"]);
+    for athlete in overview.athletes.iter().filter(|athlete| !athlete.hide) {
+        athletes.row(vec![
+            athlete.athlete_id.to_string(),
+            athlete.full_name(),
+            athlete.country_code.alpha2().to_string(),
+            athlete.team.clone(),
+            athlete.lt_slug.clone(),
+        ]);
+    }
+    let mut turnpoints = Table::new(&[
+        "ID",
+        "Turnpoint",
+        "Country",
+        "Lat",
+        "Lng",
+        "Radius (m)",
+        "To goal (km)",
+    ]);
+    for turnpoint in &overview.turnpoints {
+        turnpoints.row(vec![
+            turnpoint.id.to_string(),
+            turnpoint.header.clone(),
+            turnpoint.country_code.alpha2().to_string(),
+            format!("{:.4}", turnpoint.lat),
+            format!("{:.4}", turnpoint.lng),
+            turnpoint.cylinderradius.to_string(),
+            format!("{:.1}", turnpoint.dist_to_goal),
+        ]);
+    }
+    print!("{}\n{}", athletes, turnpoints);
+    Ok(())
+}
+
+fn status_table(overview: &Overview, status: &[RaceStatus], now: DateTime<Utc>) -> Table {
+    let mut status: Vec<&RaceStatus> = status.iter().collect();
+    status.sort_by(|a, b| a.distance_to_goal.total_cmp(&b.distance_to_goal));
+    let mut table = Table::new(&[
+        "Rank",
+        "Athlete",
+        "Country",
+        "Status",
+        "To goal (km)",
+        "Altitude (m)",
+        "Updated",
+    ]);
+    let index = overview.athlete_index();
+    for (rank, status) in status.into_iter().enumerate() {
+        let athlete = index.by_id(status.athlete_id);
+        table.row(vec![
+            (rank + 1).to_string(),
+            athlete.map_or_else(|| status.athlete_id.to_string(), Athlete::full_name),
+            athlete.map_or_else(String::new, |athlete| {
+                athlete.country_code.alpha2().to_string()
+            }),
+            status.status.as_str().to_string(),
+            format!("{:.1}", status.distance_to_goal),
+            status.altitude.to_string(),
+            age(status.timestamp as i64, now),
+        ]);
+    }
+    table
+}
+
+pub async fn status(json: bool) -> Result<()> {
+    let status = RaceStatus::request().await?;
+    if json {
+        return print_json(&status);
+    }
+    let overview = Overview::request().await?;
+    print!("{}", status_table(&overview, &status, Utc::now()));
+    Ok(())
+}
+
+async fn fetch_track(athlete: &Athlete, source: &TrackSource) -> Result<Track> {
+    let response = match source {
+        TrackSource::Latest => ApiTrackResponse::request(athlete.athlete_id).await?,
+        TrackSource::Reduced => ApiTrackResponse::request_reduced(athlete.athlete_id).await?,
+        TrackSource::At(at) => {
+            let at = at.duration_trunc(Duration::minutes(5))?;
+            ApiTrackResponse::request_replay(athlete.athlete_id, at).await?
+        }
+    };
+    Ok(Track::from(response))
+}
+
+pub async fn track(athlete: &str, source: TrackSource, json: bool) -> Result<()> {
+    let overview = Overview::request().await?;
+    let athlete = overview.athlete_index().find(athlete)?;
+    let track = fetch_track(athlete, &source).await?;
+    if json {
+        return print_json(&track);
+    }
+    let mut table = Table::new(&[
+        "Time",
+        "Lat",
+        "Lng",
+        "Altitude (m)",
+        "AGL (m)",
+        "Status",
+        "Speed",
+        "Vario",
+    ]);
+    for point in &track.points {
+        table.row(vec![
+            point.time().format("%Y-%m-%d %H:%M:%S").to_string(),
+            format!("{:.5}", point.lat),
+            format!("{:.5}", point.lng),
+            format!("{:.0}", point.altitude),
+            format!("{:.0}", point.altitude_agl),
+            point.status.clone().unwrap_or_default(),
+            format!("{:.1}", point.speed),
+            format!("{:.1}", point.vertical_speed),
+        ]);
+    }
+    println!("{} ({} points)", athlete.full_name(), track.points.len());
+    print!("{}", table);
+    Ok(())
+}
+
+pub async fn replay(date: NaiveDate, json: bool) -> Result<()> {
+    let day = Local
+        .from_local_datetime(&date.and_hms_opt(12, 0, 0).unwrap())
+        .earliest()
+        .ok_or_else(|| format!("{} does not exist in the local timezone", date))?;
+    let replay = RaceStatusReplay::request(day).await?;
+    if json {
+        return print_json(&replay);
+    }
+    let overview = Overview::request().await?;
+    for snapshot in &replay {
+        let time = Utc
+            .timestamp_opt(snapshot.timestamp as i64, 0)
+            .single()
+            .unwrap_or_default();
+        println!("{}", time.format("%Y-%m-%d %H:%M:%S UTC"));
+        println!("{}", status_table(&overview, &snapshot.status, time));
+    }
+    Ok(())
+}
+
+/// Compares every JSON feed with its model, failing if any drifted or
+/// couldn't be fetched.
+pub async fn schema_check(json: bool) -> Result<()> {
+    let drift = xalps::schema::check(xalps::Client::shared()).await;
+    if json {
+        print_json(&drift)?;
+    } else {
+        for feed in &drift {
+            if let Some(error) = &feed.error {
+                println!("{}: failed to fetch ({}): {}", feed.feed, feed.url, error);
+                continue;
+            }
+            if feed.changes.is_empty() {
+                println!("{}: matches the model", feed.feed);
+                continue;
+            }
+            println!(
+                "{}: {} changes ({})",
+                feed.feed,
+                feed.changes.len(),
+                feed.url
+            );
+            for change in &feed.changes {
+                let impact = if change.breaks_decoding() {
+                    "breaks decoding"
+                } else {
+                    "ignored"
+                };
+                println!("  {} [{}]", change, impact);
+            }
+        }
+    }
+    if drift.iter().any(|feed| !feed.changes.is_empty()) {
+        return Err("the feeds drifted from the models".into());
+    }
+    if drift.iter().any(|feed| feed.error.is_some()) {
+        return Err("some feeds could not be checked".into());
+    }
+    Ok(())
+}
+
+pub async fn export(
+    athlete: &str,
+    source: TrackSource,
+    format: Format,
+    output: Option<&Path>,
+) -> Result<()> {
+    let overview = Overview::request().await?;
+    let athlete = overview.athlete_index().find(athlete)?;
+    let track = fetch_track(athlete, &source).await?;
+    match output {
+        Some(path) => {
+            let mut file = File::create(path)?;
+            format.write(&track, &athlete.full_name(), &mut file)?;
+            file.flush()?;
+        }
+        None => format.write(&track, &athlete.full_name(), &mut std::io::stdout().lock())?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::age;
+    use chrono::{TimeZone, Utc};
+
+    #[test]
+    fn ages() {
+        let now = Utc.timestamp_opt(100_000, 0).unwrap();
+        assert_eq!(age(99_990, now), "10s");
+        assert_eq!(age(100_000 - 3 * 3600 - 120, now), "3h 2m");
+    }
+}
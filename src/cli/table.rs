@@ -0,0 +1,76 @@
+use std::fmt;
+
+/// Plain text table with left aligned columns.
+pub struct Table {
+    header: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+impl Table {
+    pub fn new(header: &[&str]) -> Table {
+        Table {
+            header: header.iter().map(|cell| cell.to_string()).collect(),
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn row(&mut self, cells: Vec<String>) {
+        self.rows.push(cells);
+    }
+
+    fn widths(&self) -> Vec<usize> {
+        let mut widths: Vec<usize> = self
+            .header
+            .iter()
+            .map(|cell| cell.chars().count())
+            .collect();
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+        widths
+    }
+}
+
+fn write_row(f: &mut fmt::Formatter<'_>, widths: &[usize], cells: &[String]) -> fmt::Result {
+    let line = widths
+        .iter()
+        .zip(cells)
+        .map(|(width, cell)| format!("{}{}", cell, " ".repeat(width - cell.chars().count())))
+        .collect::<Vec<_>>()
+        .join("  ");
+    writeln!(f, "{}", line.trim_end())
+}
+
+impl fmt::Display for Table {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let widths = self.widths();
+        write_row(f, &widths, &self.header)?;
+        let rule: Vec<String> = widths.iter().map(|width| "-".repeat(*width)).collect();
+        write_row(f, &widths, &rule)?;
+        for row in &self.rows {
+            write_row(f, &widths, row)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Table;
+
+    #[test]
+    fn aligns_columns() {
+        let mut table = Table::new(&["ID", "Name"]);
+        table.row(vec!["3".into(), "Patrick von Känel".into()]);
+        table.row(vec!["29".into(), "Christian Maurer".into()]);
+        assert_eq!(
+            table.to_string(),
+            "ID  Name\n\
+             --  -----------------\n\
+             3   Patrick von Känel\n\
+             29  Christian Maurer\n"
+        );
+    }
+}
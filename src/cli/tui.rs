@@ -0,0 +1,293 @@
+//! `xalps tui`, a live leaderboard in the terminal.
+
+use super::{age, Result};
+use chrono::{DateTime, Utc};
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    text::Line,
+    widgets::{Block, Cell, Paragraph, Row, Sparkline, Table, TableState},
+    DefaultTerminal, Frame,
+};
+use std::time::{Duration, Instant};
+use xalps::{ApiTrackResponse, AthleteId, AthleteStatus, Overview, RaceStatus, Track};
+
+/// How long to wait for key presses before redrawing the countdown.
+const TICK: Duration = Duration::from_millis(250);
+
+/// One line of the leaderboard.
+#[derive(Debug, PartialEq)]
+struct Entry {
+    athlete_id: AthleteId,
+    name: String,
+    country: String,
+    status: AthleteStatus,
+    distance_to_goal: f64,
+    altitude: usize,
+    timestamp: i64,
+}
+
+/// Race status joined with the athletes of the overview, leader first.
+fn leaderboard(overview: &Overview, status: &[RaceStatus]) -> Vec<Entry> {
+    let index = overview.athlete_index();
+    let mut entries: Vec<Entry> = status
+        .iter()
+        .filter_map(|status| {
+            let athlete = index.by_id(status.athlete_id)?;
+            (!athlete.hide).then(|| Entry {
+                athlete_id: status.athlete_id,
+                name: athlete.full_name(),
+                country: athlete.country_code.alpha2().to_string(),
+                status: status.status,
+                distance_to_goal: status.distance_to_goal,
+                altitude: status.altitude,
+                timestamp: status.timestamp as i64,
+            })
+        })
+        .collect();
+    entries.sort_by(|a, b| a.distance_to_goal.total_cmp(&b.distance_to_goal));
+    entries
+}
+
+fn icon(status: AthleteStatus) -> &'static str {
+    match status {
+        AthleteStatus::Fly => "🪂",
+        AthleteStatus::Hike => "🥾",
+        AthleteStatus::Rest => "⛺",
+    }
+}
+
+struct Dashboard {
+    overview: Overview,
+    entries: Vec<Entry>,
+    table: TableState,
+    track: Option<Track>,
+    interval: Duration,
+    next_poll: Instant,
+    updated: Option<DateTime<Utc>>,
+    error: Option<String>,
+}
+
+impl Dashboard {
+    fn selected(&self) -> Option<&Entry> {
+        self.table
+            .selected()
+            .and_then(|index| self.entries.get(index))
+    }
+
+    async fn poll(&mut self) {
+        self.next_poll = Instant::now() + self.interval;
+        let selected = self.selected().map(|entry| entry.athlete_id);
+        match RaceStatus::request().await {
+            Ok(status) => {
+                self.entries = leaderboard(&self.overview, &status);
+                let index = selected
+                    .and_then(|id| self.entries.iter().position(|entry| entry.athlete_id == id))
+                    .or((!self.entries.is_empty()).then_some(0));
+                self.table.select(index);
+                self.updated = Some(Utc::now());
+                self.error = None;
+            }
+            Err(err) => self.error = Some(format!("failed to fetch race status: {}", err)),
+        }
+        self.fetch_track().await;
+    }
+
+    async fn fetch_track(&mut self) {
+        let Some(athlete_id) = self.selected().map(|entry| entry.athlete_id) else {
+            self.track = None;
+            return;
+        };
+        match ApiTrackResponse::request(athlete_id).await {
+            Ok(response) => self.track = Some(Track::from(response)),
+            Err(err) => {
+                self.track = None;
+                self.error = Some(format!("failed to fetch track: {}", err));
+            }
+        }
+    }
+
+    async fn select(&mut self, offset: isize) {
+        if self.entries.is_empty() {
+            return;
+        }
+        let last = self.entries.len() as isize - 1;
+        let index = self.table.selected().map_or(0, |index| index as isize);
+        self.table
+            .select(Some((index + offset).clamp(0, last) as usize));
+        self.fetch_track().await;
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [header, board, altitude, footer] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Min(5),
+            Constraint::Length(8),
+            Constraint::Length(1),
+        ])
+        .areas(frame.area());
+
+        let countdown = self
+            .next_poll
+            .saturating_duration_since(Instant::now())
+            .as_secs();
+        let updated = self.updated.map_or_else(
+            || "never".to_string(),
+            |updated| updated.format("%H:%M:%S UTC").to_string(),
+        );
+        frame.render_widget(
+            Paragraph::new(format!(
+                "Red Bull X-Alps  updated {}  next poll in {}s",
+                updated, countdown
+            ))
+            .style(Style::new().add_modifier(Modifier::BOLD)),
+            header,
+        );
+
+        let now = Utc::now();
+        let rows = self.entries.iter().enumerate().map(|(rank, entry)| {
+            Row::new([
+                Cell::from((rank + 1).to_string()),
+                Cell::from(entry.name.clone()),
+                Cell::from(entry.country.clone()),
+                Cell::from(format!("{} {}", icon(entry.status), entry.status.as_str())),
+                Cell::from(Line::from(format!("{:.1}", entry.distance_to_goal)).right_aligned()),
+                Cell::from(Line::from(entry.altitude.to_string()).right_aligned()),
+                Cell::from(age(entry.timestamp, now)),
+            ])
+        });
+        let table = Table::new(
+            rows,
+            [
+                Constraint::Length(4),
+                Constraint::Min(20),
+                Constraint::Length(7),
+                Constraint::Length(8),
+                Constraint::Length(12),
+                Constraint::Length(12),
+                Constraint::Length(8),
+            ],
+        )
+        .header(
+            Row::new([
+                "Rank",
+                "Athlete",
+                "Country",
+                "Status",
+                "To goal (km)",
+                "Altitude (m)",
+                "Updated",
+            ])
+            .style(Style::new().add_modifier(Modifier::BOLD)),
+        )
+        .row_highlight_style(Style::new().add_modifier(Modifier::REVERSED))
+        .block(Block::bordered().title("Leaderboard"));
+        frame.render_stateful_widget(table, board, &mut self.table);
+
+        let width = altitude.width.saturating_sub(2) as usize;
+        let (title, data) = match (&self.track, self.selected()) {
+            (Some(track), Some(entry)) if !track.points.is_empty() => {
+                let points = &track.points[track.points.len().saturating_sub(width)..];
+                let min = points
+                    .iter()
+                    .map(|point| point.altitude)
+                    .fold(f32::INFINITY, f32::min);
+                let max = points
+                    .iter()
+                    .map(|point| point.altitude)
+                    .fold(f32::NEG_INFINITY, f32::max);
+                (
+                    format!("Altitude of {}  {:.0} - {:.0} m", entry.name, min, max),
+                    points
+                        .iter()
+                        .map(|point| (point.altitude - min) as u64 + 1)
+                        .collect(),
+                )
+            }
+            (_, Some(entry)) => (format!("Altitude of {}  no track", entry.name), Vec::new()),
+            _ => ("Altitude".to_string(), Vec::new()),
+        };
+        frame.render_widget(
+            Sparkline::default()
+                .block(Block::bordered().title(title))
+                .data(data),
+            altitude,
+        );
+
+        let footer_text = match &self.error {
+            Some(error) => error.clone(),
+            None => "↑/↓ select  r refresh  q quit".to_string(),
+        };
+        frame.render_widget(Paragraph::new(footer_text), footer);
+    }
+}
+
+/// Runs the dashboard until `q` or Esc is pressed, polling every `interval`.
+pub async fn run(interval: Duration) -> Result<()> {
+    let overview = Overview::request().await?;
+    let mut dashboard = Dashboard {
+        overview,
+        entries: Vec::new(),
+        table: TableState::default(),
+        track: None,
+        interval,
+        next_poll: Instant::now(),
+        updated: None,
+        error: None,
+    };
+    let mut terminal = ratatui::init();
+    let result = event_loop(&mut terminal, &mut dashboard).await;
+    ratatui::restore();
+    result
+}
+
+async fn event_loop(terminal: &mut DefaultTerminal, dashboard: &mut Dashboard) -> Result<()> {
+    loop {
+        if Instant::now() >= dashboard.next_poll {
+            dashboard.poll().await;
+        }
+        terminal.draw(|frame| dashboard.draw(frame))?;
+        if !event::poll(TICK)? {
+            continue;
+        }
+        let Event::Key(key) = event::read()? else {
+            continue;
+        };
+        if key.kind != KeyEventKind::Press {
+            continue;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('r') => dashboard.next_poll = Instant::now(),
+            KeyCode::Up | KeyCode::Char('k') => dashboard.select(-1).await,
+            KeyCode::Down | KeyCode::Char('j') => dashboard.select(1).await,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::leaderboard;
+    use xalps::{AthleteId, AthleteStatus, Overview, RaceStatus};
+
+    #[test]
+    fn joins_status_with_athletes() {
+        let overview: Overview = serde_json::from_slice(include_bytes!(
+            "../../fixtures/fileadmin/live-tracking/2023/race/feeds/cdn-long/overview.json"
+        ))
+        .unwrap();
+        let mut status: Vec<RaceStatus> =
+            serde_json::from_slice(include_bytes!("../../fixtures/race/race-status")).unwrap();
+        status.reverse();
+        let entries = leaderboard(&overview, &status);
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].athlete_id, AthleteId::new(29));
+        assert_eq!(entries[0].country, "CH");
+        assert_eq!(entries[0].status, AthleteStatus::Fly);
+        assert!(entries
+            .windows(2)
+            .all(|pair| pair[0].distance_to_goal <= pair[1].distance_to_goal));
+    }
+}
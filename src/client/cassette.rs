@@ -0,0 +1,195 @@
+use crate::Error;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+};
+
+/// Directory of recorded responses, see [`ClientBuilder::cassette`](super::ClientBuilder::cassette).
+///
+/// Every response is stored as `{n}.json` (url, status, headers) and `{n}.body`
+/// (the raw body, so recordings can be used as fixtures directly), numbered in
+/// the order they were fetched.
+#[derive(Debug)]
+pub struct Cassette {
+    dir: PathBuf,
+    mode: Mode,
+}
+
+#[derive(Debug)]
+enum Mode {
+    Record {
+        next: AtomicUsize,
+    },
+    /// Recordings per url path, in order. The last one is kept once the others are used up.
+    Replay {
+        recordings: Mutex<HashMap<String, Vec<Recording>>>,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Recording {
+    pub url: String,
+    pub status: u16,
+    pub headers: BTreeMap<String, String>,
+    #[serde(skip)]
+    pub body: Vec<u8>,
+}
+
+impl Cassette {
+    /// Records every response into `dir`, after any recordings already in it.
+    pub fn record(dir: impl Into<PathBuf>) -> Result<Cassette, Error> {
+        let dir = dir.into();
+        fs::create_dir_all(&dir)?;
+        let next = Cassette::load(&dir)?.len();
+        Ok(Cassette {
+            dir,
+            mode: Mode::Record {
+                next: AtomicUsize::new(next),
+            },
+        })
+    }
+
+    /// Answers requests from the recordings in `dir` instead of the network.
+    pub fn replay(dir: impl Into<PathBuf>) -> Result<Cassette, Error> {
+        let dir = dir.into();
+        let mut recordings: HashMap<String, Vec<Recording>> = HashMap::new();
+        for recording in Cassette::load(&dir)? {
+            recordings
+                .entry(Cassette::key(&recording.url))
+                .or_default()
+                .push(recording);
+        }
+        Ok(Cassette {
+            dir,
+            mode: Mode::Replay {
+                recordings: Mutex::new(recordings),
+            },
+        })
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    pub fn is_replaying(&self) -> bool {
+        matches!(self.mode, Mode::Replay { .. })
+    }
+
+    /// All recordings in `dir`, in the order they were fetched.
+    pub fn load(dir: &Path) -> Result<Vec<Recording>, Error> {
+        let mut recordings = Vec::new();
+        if !dir.exists() {
+            return Ok(recordings);
+        }
+        let mut files = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.path()))
+            .collect::<Result<Vec<_>, _>>()?;
+        files.retain(|path| {
+            path.extension()
+                .is_some_and(|extension| extension == "json")
+        });
+        files.sort();
+        for path in files {
+            let mut recording: Recording = serde_json::from_slice(&fs::read(&path)?)?;
+            recording.body = fs::read(path.with_extension("body"))?;
+            recordings.push(recording);
+        }
+        Ok(recordings)
+    }
+
+    pub(crate) fn save(&self, recording: &Recording) -> Result<(), Error> {
+        if let Mode::Record { next } = &self.mode {
+            let name = format!("{:05}", next.fetch_add(1, Ordering::SeqCst));
+            fs::write(
+                self.dir.join(&name).with_extension("json"),
+                serde_json::to_vec_pretty(recording)?,
+            )?;
+            fs::write(self.dir.join(name).with_extension("body"), &recording.body)?;
+        }
+        Ok(())
+    }
+
+    /// Next recording for `url` and whether it is the last one, `None` when
+    /// recording or if `url` was never recorded.
+    pub(crate) fn next(&self, url: &str) -> Option<(Recording, bool)> {
+        match &self.mode {
+            Mode::Record { .. } => None,
+            Mode::Replay { recordings } => {
+                let mut recordings = recordings.lock().unwrap();
+                let queue = recordings.get_mut(&Cassette::key(url))?;
+                if queue.len() > 1 {
+                    Some((queue.remove(0), false))
+                } else {
+                    queue.first().cloned().map(|recording| (recording, true))
+                }
+            }
+        }
+    }
+
+    /// Recordings are matched by path and query only, so a cassette recorded
+    /// against one host can be replayed by a client configured for another.
+    fn key(url: &str) -> String {
+        match reqwest::Url::parse(url) {
+            Ok(url) => match url.query() {
+                Some(query) => format!("{}?{}", url.path(), query),
+                None => url.path().to_string(),
+            },
+            Err(_) => url.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Cassette;
+    use crate::{test_util::MockServer, ApiTrackResponse, AthleteId, Client, Overview, RaceStatus};
+
+    #[tokio::test]
+    async fn record_then_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let server = MockServer::start().await;
+        server.fail("/race/race-status", 503, 1);
+        let recorder = server
+            .client_builder()
+            .cassette(Cassette::record(dir.path()).unwrap())
+            .build()
+            .unwrap();
+        let overview = Overview::request_with(&recorder).await.unwrap();
+        let status = RaceStatus::request_with(&recorder).await.unwrap();
+        let track = ApiTrackResponse::request_with(&recorder, AthleteId::new(29))
+            .await
+            .unwrap();
+        drop(server);
+
+        assert_eq!(Cassette::load(dir.path()).unwrap().len(), 4);
+
+        let player = Client::builder()
+            .base_url("http://127.0.0.1:9")
+            .cassette(Cassette::replay(dir.path()).unwrap())
+            .build()
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", Overview::request_with(&player).await.unwrap()),
+            format!("{:?}", overview)
+        );
+        assert_eq!(
+            format!("{:?}", RaceStatus::request_with(&player).await.unwrap()),
+            format!("{:?}", status)
+        );
+        assert_eq!(
+            ApiTrackResponse::request_with(&player, AthleteId::new(29))
+                .await
+                .unwrap(),
+            track
+        );
+        assert!(ApiTrackResponse::request_with(&player, AthleteId::new(3))
+            .await
+            .is_err());
+    }
+}
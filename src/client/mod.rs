@@ -0,0 +1,289 @@
+mod cassette;
+mod observer;
+mod policy;
+
+pub use self::{
+    cassette::{Cassette, Recording},
+    observer::{endpoint, FeedObserver},
+    policy::{RateLimit, RateLimiter, RequestPolicy},
+};
+
+use crate::{DecodeReport, Error, Track};
+use serde::de::DeserializeOwned;
+use std::sync::{Arc, OnceLock};
+
+// The browser has neither tokio's timer nor a monotonic clock in std.
+#[cfg(target_arch = "wasm32")]
+pub(crate) use {gloo_timers::future::sleep, web_time::Instant};
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) use {std::time::Instant, tokio::time::sleep};
+
+/// Host of `overview.json`.
+pub const WEBSITE_URL: &str = "https://www.redbullxalps.com";
+/// Host of the race status and track feeds.
+pub const DATA_URL: &str = "https://rbxltdata.redbullxalps.com";
+
+static SHARED: OnceLock<Client> = OnceLock::new();
+
+/// HTTP client used for all feed requests.
+///
+/// Cloning is cheap; clones share the connection pool and the rate limiter.
+#[derive(Debug, Clone)]
+pub struct Client {
+    http: reqwest::Client,
+    policy: RequestPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    website_url: String,
+    data_url: String,
+    cassette: Option<Arc<Cassette>>,
+    observer: Option<Arc<dyn FeedObserver>>,
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Client::builder()
+            .build()
+            .expect("failed to initialise the http client")
+    }
+}
+
+impl Client {
+    pub fn new() -> Client {
+        Client::default()
+    }
+
+    pub fn builder() -> ClientBuilder {
+        ClientBuilder::default()
+    }
+
+    /// Client used by the `request*` functions that don't take one.
+    pub fn shared() -> &'static Client {
+        SHARED.get_or_init(Client::default)
+    }
+
+    /// Makes `client` the [`shared`](Client::shared) one, e.g. to record every
+    /// request into a cassette. Returns `false` if the shared client is already in use.
+    pub fn set_shared(client: Client) -> bool {
+        SHARED.set(client).is_ok()
+    }
+
+    /// A clone reporting to `observer` instead, see [`ClientBuilder::observer`].
+    pub fn with_observer(&self, observer: Arc<dyn FeedObserver>) -> Client {
+        Client {
+            observer: Some(observer),
+            ..self.clone()
+        }
+    }
+
+    pub fn policy(&self) -> &RequestPolicy {
+        &self.policy
+    }
+
+    /// Url of `path` on the website host, e.g. `fileadmin/live-tracking/...`.
+    pub(crate) fn website_url(&self, path: &str) -> String {
+        format!("{}/{}", self.website_url, path)
+    }
+
+    /// Url of `path` on the data host, e.g. `race/race-status`.
+    pub(crate) fn data_url(&self, path: &str) -> String {
+        format!("{}/{}", self.data_url, path)
+    }
+
+    pub fn cassette(&self) -> Option<&Cassette> {
+        self.cassette.as_deref()
+    }
+
+    /// GETs `url`, retrying transient failures according to the policy.
+    pub(crate) async fn get(&self, url: &str) -> Result<Vec<u8>, Error> {
+        if let Some(cassette) = self.cassette.as_deref().filter(|c| c.is_replaying()) {
+            return Client::replay(cassette, url);
+        }
+        let mut attempt = 0;
+        loop {
+            if let Some(limiter) = &self.limiter {
+                limiter.acquire().await;
+            }
+            let started = Instant::now();
+            let result = self.send(url).await;
+            if let Some(observer) = &self.observer {
+                observer.request(&endpoint(url), started.elapsed(), result.as_ref().err());
+            }
+            match result {
+                Ok(body) => return Ok(body),
+                Err(err) if attempt < self.policy.max_retries && err.is_transient() => {
+                    sleep(self.policy.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    /// Answers from the cassette, retrying recorded failures without waiting
+    /// so a recorded retry sequence replays the same way.
+    fn replay(cassette: &Cassette, url: &str) -> Result<Vec<u8>, Error> {
+        loop {
+            let (recording, last) = cassette
+                .next(url)
+                .ok_or_else(|| Error::NotRecorded(url.to_string()))?;
+            if recording.status < 400 {
+                return Ok(recording.body);
+            }
+            let err = Error::Status {
+                url: url.to_string(),
+                status: recording.status,
+            };
+            if last || !err.is_transient() {
+                return Err(err);
+            }
+        }
+    }
+
+    pub(crate) async fn get_json<T: DeserializeOwned>(&self, url: &str) -> Result<T, Error> {
+        serde_json::from_slice(&self.get(url).await?)
+            .map_err(|err| self.decode_failed(url, "json", err.into()))
+    }
+
+    pub(crate) async fn get_protobuf<T: protobuf::Message>(&self, url: &str) -> Result<T, Error> {
+        protobuf::Message::parse_from_bytes(&self.get(url).await?)
+            .map_err(|err| self.decode_failed(url, "protobuf", err.into()))
+    }
+
+    pub(crate) async fn get_track_lenient(
+        &self,
+        url: &str,
+    ) -> Result<(Track, DecodeReport), Error> {
+        Track::decode_lenient(&self.get(url).await?)
+            .map_err(|err| self.decode_failed(url, "protobuf", err))
+    }
+
+    fn decode_failed(&self, url: &str, format: &str, err: Error) -> Error {
+        if let Some(observer) = &self.observer {
+            observer.decode_failed(&endpoint(url), format, &err);
+        }
+        err
+    }
+
+    async fn send(&self, url: &str) -> Result<Vec<u8>, Error> {
+        let request = self.http.get(url);
+        #[cfg(not(target_arch = "wasm32"))]
+        let request = request.timeout(self.policy.timeout);
+        let response = request.send().await?;
+        let status = response
+            .error_for_status_ref()
+            .map(|_| ())
+            .map_err(Error::from);
+        let Some(cassette) = &self.cassette else {
+            status?;
+            return Ok(response.bytes().await?.to_vec());
+        };
+        let mut recording = Recording {
+            url: url.to_string(),
+            status: response.status().as_u16(),
+            headers: response
+                .headers()
+                .iter()
+                .map(|(name, value)| {
+                    (
+                        name.to_string(),
+                        String::from_utf8_lossy(value.as_bytes()).into_owned(),
+                    )
+                })
+                .collect(),
+            body: Vec::new(),
+        };
+        recording.body = response.bytes().await?.to_vec();
+        cassette.save(&recording)?;
+        status.map(|_| recording.body)
+    }
+}
+
+#[derive(Debug)]
+pub struct ClientBuilder {
+    policy: RequestPolicy,
+    limiter: Option<Arc<RateLimiter>>,
+    website_url: String,
+    data_url: String,
+    cassette: Option<Cassette>,
+    observer: Option<Arc<dyn FeedObserver>>,
+}
+
+impl Default for ClientBuilder {
+    fn default() -> Self {
+        ClientBuilder {
+            policy: RequestPolicy::default(),
+            limiter: None,
+            website_url: WEBSITE_URL.to_string(),
+            data_url: DATA_URL.to_string(),
+            cassette: None,
+            observer: None,
+        }
+    }
+}
+
+impl ClientBuilder {
+    pub fn policy(mut self, policy: RequestPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Shares an existing rate limiter instead of creating one from the policy,
+    /// e.g. to put several clients under the same limit.
+    pub fn rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Serves both hosts from `url`, e.g. a mirror or a local mock server
+    /// that uses the same paths as the real feeds.
+    pub fn base_url(self, url: &str) -> Self {
+        self.website_url(url).data_url(url)
+    }
+
+    /// Replaces [`WEBSITE_URL`].
+    pub fn website_url(mut self, url: &str) -> Self {
+        self.website_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Replaces [`DATA_URL`].
+    pub fn data_url(mut self, url: &str) -> Self {
+        self.data_url = url.trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Records every response into the cassette, or answers from it when it
+    /// was opened with [`Cassette::replay`].
+    pub fn cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+
+    /// Reports the latency and outcome of every request and every body that
+    /// fails to decode, e.g. to `metrics::Metrics` with the `metrics` feature.
+    pub fn observer(mut self, observer: Arc<dyn FeedObserver>) -> Self {
+        self.observer = Some(observer);
+        self
+    }
+
+    pub fn build(self) -> Result<Client, Error> {
+        let limiter = self.limiter.or_else(|| {
+            self.policy
+                .rate_limit
+                .map(|limit| Arc::new(RateLimiter::new(limit)))
+        });
+        let http = reqwest::Client::builder();
+        // Fetch in the browser has no timeouts.
+        #[cfg(not(target_arch = "wasm32"))]
+        let http = http.connect_timeout(self.policy.timeout);
+        Ok(Client {
+            http: http.build()?,
+            policy: self.policy,
+            limiter,
+            website_url: self.website_url,
+            data_url: self.data_url,
+            cassette: self.cassette.map(Arc::new),
+            observer: self.observer,
+        })
+    }
+}
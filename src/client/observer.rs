@@ -0,0 +1,65 @@
+use crate::Error;
+use std::{fmt, time::Duration};
+
+/// Hook for watching the health of the feeds, see [`ClientBuilder::observer`](super::ClientBuilder::observer).
+///
+/// Endpoints are urls reduced by [`endpoint`], so they can be used as metric labels.
+pub trait FeedObserver: fmt::Debug + Send + Sync {
+    /// A request finished after `latency`, with `error` if it failed. Called once per attempt.
+    fn request(&self, endpoint: &str, latency: Duration, error: Option<&Error>);
+
+    /// A fetched body is not valid `format`, either `"json"` or `"protobuf"`.
+    fn decode_failed(&self, endpoint: &str, format: &str, error: &Error);
+}
+
+/// Path of `url` with athlete ids, dates and times replaced by placeholders,
+/// e.g. `/race/athlete/{id}/track/latest.pbf`.
+pub fn endpoint(url: &str) -> String {
+    let path = match reqwest::Url::parse(url) {
+        Ok(url) => url.path().to_string(),
+        Err(_) => url.to_string(),
+    };
+    let mut endpoint = String::new();
+    let mut parent = "";
+    for segment in path.split('/').filter(|segment| !segment.is_empty()) {
+        endpoint.push('/');
+        if !segment.is_empty() && segment.bytes().all(|byte| byte.is_ascii_digit()) {
+            endpoint.push_str("{id}");
+        } else if segment.starts_with("race-status-replay_") {
+            endpoint.push_str("race-status-replay_{date}");
+        } else if parent == "latest-replay" {
+            endpoint.push_str("{time}.pbf");
+        } else {
+            endpoint.push_str(segment);
+        }
+        parent = segment;
+    }
+    endpoint
+}
+
+#[cfg(test)]
+mod test {
+    use super::endpoint;
+
+    #[test]
+    fn endpoints() {
+        assert_eq!(
+            endpoint("https://rbxltdata.redbullxalps.com/race/athlete/29/track/latest.pbf"),
+            "/race/athlete/{id}/track/latest.pbf"
+        );
+        assert_eq!(
+            endpoint(
+                "http://127.0.0.1:1/race/athlete/3/track/latest-replay/2023-06-14T16:50:00Z.pbf"
+            ),
+            "/race/athlete/{id}/track/latest-replay/{time}.pbf"
+        );
+        assert_eq!(
+            endpoint("https://rbxltdata.redbullxalps.com/race/race-status-replay_2023-06-14"),
+            "/race/race-status-replay_{date}"
+        );
+        assert_eq!(
+            endpoint("https://rbxltdata.redbullxalps.com/race/race-status"),
+            "/race/race-status"
+        );
+    }
+}
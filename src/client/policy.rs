@@ -0,0 +1,176 @@
+use super::{sleep, Instant};
+use std::{sync::Mutex, time::Duration};
+
+/// How the [`Client`](super::Client) sends requests to the feeds.
+#[derive(Debug, Clone)]
+pub struct RequestPolicy {
+    /// Upper bound for a single attempt, including reading the body.
+    pub timeout: Duration,
+    /// How many times a request is repeated after a transient failure.
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled on every further attempt.
+    pub initial_backoff: Duration,
+    /// Cap for the exponential backoff.
+    pub max_backoff: Duration,
+    /// Limit shared by every request of a client, `None` disables it.
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl Default for RequestPolicy {
+    fn default() -> Self {
+        RequestPolicy {
+            timeout: Duration::from_secs(30),
+            max_retries: 3,
+            initial_backoff: Duration::from_millis(500),
+            max_backoff: Duration::from_secs(30),
+            rate_limit: Some(RateLimit {
+                per_second: 5.0,
+                burst: 10,
+            }),
+        }
+    }
+}
+
+impl RequestPolicy {
+    /// Policy that sends every request exactly once and without limit.
+    pub fn no_retry() -> Self {
+        RequestPolicy {
+            max_retries: 0,
+            rate_limit: None,
+            ..RequestPolicy::default()
+        }
+    }
+
+    /// Delay before retry number `attempt` (starting at 0), with "equal jitter":
+    /// half of the exponential delay is fixed, the other half is random.
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponential = self
+            .initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_backoff);
+        let half = exponential / 2;
+        half + half.mul_f64(fastrand::f64())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimit {
+    /// Tokens added to the bucket per second. Zero, negative, infinite or
+    /// NaN rates don't limit at all.
+    pub per_second: f64,
+    /// Size of the bucket, i.e. how many requests may be sent at once.
+    pub burst: u32,
+}
+
+/// Token bucket shared by all clones of a [`Client`](super::Client).
+#[derive(Debug)]
+pub struct RateLimiter {
+    limit: RateLimit,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+impl RateLimiter {
+    pub fn new(limit: RateLimit) -> Self {
+        RateLimiter {
+            limit,
+            bucket: Mutex::new(Bucket {
+                tokens: limit.burst as f64,
+                refilled_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// Waits until a token is available and takes it.
+    pub async fn acquire(&self) {
+        while let Some(wait) = self.try_acquire() {
+            sleep(wait).await;
+        }
+    }
+
+    /// Takes a token if one is available, otherwise returns how long to wait for the next one.
+    fn try_acquire(&self) -> Option<Duration> {
+        if !(self.limit.per_second > 0.0 && self.limit.per_second.is_finite()) {
+            return None;
+        }
+        let mut bucket = self.bucket.lock().unwrap();
+        let now = Instant::now();
+        let elapsed = now.duration_since(bucket.refilled_at).as_secs_f64();
+        bucket.tokens =
+            (bucket.tokens + elapsed * self.limit.per_second).min(self.limit.burst.max(1) as f64);
+        bucket.refilled_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            None
+        } else {
+            // Tiny rates would overflow a `Duration`, they mean waiting forever.
+            Some(
+                Duration::try_from_secs_f64((1.0 - bucket.tokens) / self.limit.per_second)
+                    .unwrap_or(Duration::MAX),
+            )
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{RateLimit, RateLimiter, RequestPolicy};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = RequestPolicy {
+            initial_backoff: Duration::from_millis(100),
+            max_backoff: Duration::from_secs(1),
+            ..RequestPolicy::default()
+        };
+        for attempt in 0..10 {
+            let expected =
+                (Duration::from_millis(100) * 2u32.pow(attempt)).min(Duration::from_secs(1));
+            let backoff = policy.backoff(attempt);
+            assert!(backoff >= expected / 2 && backoff <= expected);
+        }
+    }
+
+    #[test]
+    fn invalid_rate_is_unlimited() {
+        for per_second in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            let limiter = RateLimiter::new(RateLimit {
+                per_second,
+                burst: 1,
+            });
+            for _ in 0..3 {
+                assert_eq!(limiter.try_acquire(), None);
+            }
+        }
+    }
+
+    #[test]
+    fn tiny_rate_waits_forever() {
+        let limiter = RateLimiter::new(RateLimit {
+            per_second: 1e-20,
+            burst: 1,
+        });
+        assert_eq!(limiter.try_acquire(), None);
+        assert_eq!(limiter.try_acquire(), Some(Duration::MAX));
+    }
+
+    #[tokio::test]
+    async fn rate_limiter_waits_after_burst() {
+        let limiter = RateLimiter::new(RateLimit {
+            per_second: 20.0,
+            burst: 2,
+        });
+        let start = Instant::now();
+        limiter.acquire().await;
+        limiter.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(40));
+        limiter.acquire().await;
+        assert!(start.elapsed() >= Duration::from_millis(40));
+    }
+}
@@ -0,0 +1,143 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The request failed, timed out or the server answered with an error status.
+    Http(reqwest::Error),
+    /// The response body is not valid JSON for the requested feed.
+    Json(serde_json::Error),
+    /// The response body is not a valid protobuf message.
+    Protobuf(protobuf::ProtobufError),
+    /// A replayed recording answered with an error status.
+    Status { url: String, status: u16 },
+    /// Replaying a cassette that has no recording for the url.
+    NotRecorded(String),
+    /// A track can't be encoded in the feed format, e.g. a point has a NaN or out of range coordinate.
+    InvalidTrack(String),
+    /// Reading or writing local files failed.
+    Io(std::io::Error),
+    /// A config value is out of range, e.g. a zero interval.
+    InvalidConfig(String),
+    /// The local archive could not be read or written.
+    #[cfg(feature = "storage")]
+    Storage(rusqlite::Error),
+    /// The proxy server could not bind or failed while serving.
+    #[cfg(feature = "server")]
+    Server(hyper::Error),
+}
+
+impl Error {
+    /// HTTP status upstream answered with, if it answered at all.
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Error::Http(err) => err.status().map(|status| status.as_u16()),
+            Error::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Whether retrying the same request might succeed.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Error::Http(err) => {
+                #[cfg(not(target_arch = "wasm32"))]
+                if err.is_connect() {
+                    return true;
+                }
+                err.is_timeout()
+                    || err.is_request()
+                    || err.is_body()
+                    || err.status().is_some_and(|status| {
+                        status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS
+                    })
+            }
+            Error::Status { status, .. } => *status >= 500 || *status == 429,
+            Error::Json(_)
+            | Error::Protobuf(_)
+            | Error::NotRecorded(_)
+            | Error::InvalidTrack(_)
+            | Error::Io(_)
+            | Error::InvalidConfig(_) => false,
+            #[cfg(feature = "storage")]
+            Error::Storage(_) => false,
+            #[cfg(feature = "server")]
+            Error::Server(_) => false,
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Http(err) => write!(f, "request failed: {}", err),
+            Error::Json(err) => write!(f, "invalid json: {}", err),
+            Error::Protobuf(err) => write!(f, "invalid protobuf: {}", err),
+            Error::Status { url, status } => write!(f, "{} answered with status {}", url, status),
+            Error::NotRecorded(url) => write!(f, "no recorded response for {}", url),
+            Error::InvalidTrack(reason) => write!(f, "invalid track: {}", reason),
+            Error::Io(err) => write!(f, "io error: {}", err),
+            Error::InvalidConfig(reason) => write!(f, "invalid config: {}", reason),
+            #[cfg(feature = "storage")]
+            Error::Storage(err) => write!(f, "storage error: {}", err),
+            #[cfg(feature = "server")]
+            Error::Server(err) => write!(f, "server error: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for Error {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            Error::Http(err) => Some(err),
+            Error::Json(err) => Some(err),
+            Error::Protobuf(err) => Some(err),
+            Error::Io(err) => Some(err),
+            #[cfg(feature = "storage")]
+            Error::Storage(err) => Some(err),
+            #[cfg(feature = "server")]
+            Error::Server(err) => Some(err),
+            Error::Status { .. }
+            | Error::NotRecorded(_)
+            | Error::InvalidTrack(_)
+            | Error::InvalidConfig(_) => None,
+        }
+    }
+}
+
+impl From<reqwest::Error> for Error {
+    fn from(err: reqwest::Error) -> Self {
+        Error::Http(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Self {
+        Error::Json(err)
+    }
+}
+
+impl From<protobuf::ProtobufError> for Error {
+    fn from(err: protobuf::ProtobufError) -> Self {
+        Error::Protobuf(err)
+    }
+}
+
+impl From<std::io::Error> for Error {
+    fn from(err: std::io::Error) -> Self {
+        Error::Io(err)
+    }
+}
+
+#[cfg(feature = "storage")]
+impl From<rusqlite::Error> for Error {
+    fn from(err: rusqlite::Error) -> Self {
+        Error::Storage(err)
+    }
+}
+
+#[cfg(feature = "server")]
+impl From<hyper::Error> for Error {
+    fn from(err: hyper::Error) -> Self {
+        Error::Server(err)
+    }
+}
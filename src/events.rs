@@ -0,0 +1,208 @@
+//! Race events derived from consecutive [`RaceStatus`] snapshots.
+
+use crate::{AthleteId, AthleteStatus, Overview, RaceStatus, TurnPointId};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum RaceEvent {
+    /// The athlete started flying.
+    #[serde(rename_all = "camelCase")]
+    Takeoff {
+        athlete_id: AthleteId,
+        timestamp: usize,
+    },
+    /// The athlete stopped flying, to hike or rest.
+    #[serde(rename_all = "camelCase")]
+    Landing {
+        athlete_id: AthleteId,
+        timestamp: usize,
+        status: AthleteStatus,
+    },
+    /// The athlete's distance to goal dropped below the turnpoint's.
+    #[serde(rename_all = "camelCase")]
+    TurnPointReached {
+        athlete_id: AthleteId,
+        timestamp: usize,
+        turnpoint_id: TurnPointId,
+        turnpoint: String,
+    },
+    /// A different athlete is now closest to goal.
+    #[serde(rename_all = "camelCase")]
+    LeadChange {
+        athlete_id: AthleteId,
+        timestamp: usize,
+        previous_leader: AthleteId,
+    },
+}
+
+impl RaceEvent {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            RaceEvent::Takeoff { .. } => "takeoff",
+            RaceEvent::Landing { .. } => "landing",
+            RaceEvent::TurnPointReached { .. } => "turnPointReached",
+            RaceEvent::LeadChange { .. } => "leadChange",
+        }
+    }
+
+    /// The athlete the event is about.
+    pub fn athlete_id(&self) -> AthleteId {
+        match self {
+            RaceEvent::Takeoff { athlete_id, .. }
+            | RaceEvent::Landing { athlete_id, .. }
+            | RaceEvent::TurnPointReached { athlete_id, .. }
+            | RaceEvent::LeadChange { athlete_id, .. } => *athlete_id,
+        }
+    }
+
+    /// Whether the event concerns the athlete, including a leader that lost the lead.
+    pub fn concerns(&self, athlete_id: AthleteId) -> bool {
+        match self {
+            RaceEvent::LeadChange {
+                previous_leader, ..
+            } if *previous_leader == athlete_id => true,
+            event => event.athlete_id() == athlete_id,
+        }
+    }
+}
+
+/// Compares every race status snapshot with the previous one.
+///
+/// The first snapshot only sets the baseline, so restarting a detector does
+/// not repeat events that happened before.
+pub struct EventDetector {
+    /// Id, name and distance to goal of every turnpoint.
+    turnpoints: Vec<(TurnPointId, String, f64)>,
+    last: HashMap<AthleteId, RaceStatus>,
+    leader: Option<AthleteId>,
+}
+
+impl EventDetector {
+    pub fn new(overview: &Overview) -> EventDetector {
+        EventDetector {
+            turnpoints: overview
+                .turnpoints
+                .iter()
+                .map(|turnpoint| {
+                    (
+                        turnpoint.id.clone(),
+                        turnpoint.header.clone(),
+                        turnpoint.dist_to_goal,
+                    )
+                })
+                .collect(),
+            last: HashMap::new(),
+            leader: None,
+        }
+    }
+
+    pub fn update(&mut self, status: &[RaceStatus]) -> Vec<RaceEvent> {
+        let mut events = Vec::new();
+        for status in status {
+            let Some(last) = self.last.get(&status.athlete_id) else {
+                continue;
+            };
+            match (last.status, status.status) {
+                (AthleteStatus::Fly, AthleteStatus::Fly) => {}
+                (_, AthleteStatus::Fly) => events.push(RaceEvent::Takeoff {
+                    athlete_id: status.athlete_id,
+                    timestamp: status.timestamp,
+                }),
+                (AthleteStatus::Fly, landed) => events.push(RaceEvent::Landing {
+                    athlete_id: status.athlete_id,
+                    timestamp: status.timestamp,
+                    status: landed,
+                }),
+                _ => {}
+            }
+            for (id, name, dist_to_goal) in &self.turnpoints {
+                if last.distance_to_goal > *dist_to_goal && status.distance_to_goal <= *dist_to_goal
+                {
+                    events.push(RaceEvent::TurnPointReached {
+                        athlete_id: status.athlete_id,
+                        timestamp: status.timestamp,
+                        turnpoint_id: id.clone(),
+                        turnpoint: name.clone(),
+                    });
+                }
+            }
+        }
+        for status in status {
+            self.last.insert(status.athlete_id, status.clone());
+        }
+
+        let leader = self
+            .last
+            .values()
+            .min_by(|a, b| a.distance_to_goal.total_cmp(&b.distance_to_goal));
+        if let Some(leader) = leader {
+            match &self.leader {
+                Some(previous) if *previous != leader.athlete_id => {
+                    events.push(RaceEvent::LeadChange {
+                        athlete_id: leader.athlete_id,
+                        timestamp: leader.timestamp,
+                        previous_leader: *previous,
+                    })
+                }
+                _ => {}
+            }
+            self.leader = Some(leader.athlete_id);
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{EventDetector, RaceEvent};
+    use crate::{test_util, AthleteId, AthleteStatus, Overview, RaceStatus};
+
+    #[test]
+    fn detects_events() {
+        let overview: Overview =
+            serde_json::from_slice(test_util::fixture(test_util::OVERVIEW_PATH).unwrap()).unwrap();
+        let status: Vec<RaceStatus> =
+            serde_json::from_slice(test_util::fixture("/race/race-status").unwrap()).unwrap();
+        let mut detector = EventDetector::new(&overview);
+        assert!(detector.update(&status).is_empty());
+        assert!(detector.update(&status).is_empty());
+
+        let mut next = status.clone();
+        // 29 lands, 3 takes off and 12 overtakes everyone past Titlis.
+        next[0].status = AthleteStatus::Rest;
+        next[1].status = AthleteStatus::Fly;
+        next[2].distance_to_goal = 680.0;
+        for status in &mut next {
+            status.timestamp += 60;
+        }
+        let events = detector.update(&next);
+        let kinds: Vec<(&str, i32)> = events
+            .iter()
+            .map(|event| (event.kind(), event.athlete_id().get()))
+            .collect();
+        assert_eq!(
+            kinds,
+            [
+                ("landing", 29),
+                ("takeoff", 3),
+                ("turnPointReached", 12),
+                ("leadChange", 12),
+            ]
+        );
+        assert!(events[3].concerns(AthleteId::new(29)));
+        assert_eq!(
+            serde_json::to_value(&events[2]).unwrap()["turnpointId"],
+            "tp6"
+        );
+        assert_eq!(serde_json::to_value(&events[0]).unwrap()["type"], "landing");
+        assert!(matches!(
+            events[0],
+            RaceEvent::Landing {
+                status: AthleteStatus::Rest,
+                ..
+            }
+        ));
+    }
+}
@@ -0,0 +1,263 @@
+//! Writing tracks in common GPS file formats.
+
+use crate::{Track, TrackPoint};
+use serde_json::json;
+use std::{fmt, io::Write, str::FromStr};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Gpx,
+    Igc,
+    Kml,
+    GeoJson,
+    Csv,
+}
+
+impl Format {
+    pub const ALL: [Format; 5] = [
+        Format::Gpx,
+        Format::Igc,
+        Format::Kml,
+        Format::GeoJson,
+        Format::Csv,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Gpx => "gpx",
+            Format::Igc => "igc",
+            Format::Kml => "kml",
+            Format::GeoJson => "geojson",
+            Format::Csv => "csv",
+        }
+    }
+
+    /// Writes `track` of the athlete called `name` in this format.
+    pub fn write(&self, track: &Track, name: &str, writer: &mut impl Write) -> std::io::Result<()> {
+        match self {
+            Format::Gpx => write_gpx(track, name, writer),
+            Format::Igc => write_igc(track, name, writer),
+            Format::Kml => write_kml(track, name, writer),
+            Format::GeoJson => write_geojson(track, name, writer),
+            Format::Csv => write_csv(track, writer),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Format::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                format!(
+                    "unknown format {}, expected one of {}",
+                    name,
+                    Format::ALL.map(|format| format.name()).join(", ")
+                )
+            })
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+fn write_gpx(track: &Track, name: &str, writer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(
+        writer,
+        r#"<gpx version="1.1" creator="xalps" xmlns="http://www.topografix.com/GPX/1/1">"#
+    )?;
+    writeln!(writer, "  <trk>")?;
+    writeln!(writer, "    <name>{}</name>", escape_xml(name))?;
+    writeln!(writer, "    <trkseg>")?;
+    for point in &track.points {
+        writeln!(
+            writer,
+            r#"      <trkpt lat="{}" lon="{}"><ele>{}</ele><time>{}</time></trkpt>"#,
+            point.lat,
+            point.lng,
+            point.altitude,
+            point.time().format("%Y-%m-%dT%H:%M:%SZ")
+        )?;
+    }
+    writeln!(writer, "    </trkseg>")?;
+    writeln!(writer, "  </trk>")?;
+    writeln!(writer, "</gpx>")
+}
+
+/// Degrees as `DDMMmmm` (latitude) or `DDDMMmmm` (longitude) followed by the hemisphere.
+fn igc_coordinate(degrees: f32, width: usize, positive: char, negative: char) -> String {
+    let hemisphere = if degrees < 0.0 { negative } else { positive };
+    let thousandths_of_minutes = (degrees.abs() as f64 * 60_000.0).round() as u64;
+    format!(
+        "{:0width$}{:05}{}",
+        thousandths_of_minutes / 60_000,
+        thousandths_of_minutes % 60_000,
+        hemisphere,
+        width = width
+    )
+}
+
+fn igc_b_record(point: &TrackPoint) -> String {
+    let altitude = point.altitude.round() as i32;
+    format!(
+        "B{}{}{}A{:05}{:05}",
+        point.time().format("%H%M%S"),
+        igc_coordinate(point.lat, 2, 'N', 'S'),
+        igc_coordinate(point.lng, 3, 'E', 'W'),
+        altitude,
+        altitude
+    )
+}
+
+fn write_igc(track: &Track, name: &str, writer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(writer, "AXXXxalps")?;
+    if let Some(first) = track.points.first() {
+        writeln!(writer, "HFDTEDATE:{},01", first.time().format("%d%m%y"))?;
+    }
+    writeln!(writer, "HFPLTPILOTINCHARGE:{}", name)?;
+    writeln!(writer, "HFCIDCOMPETITIONID:{}", track.athlete_id)?;
+    writeln!(writer, "HFGTYGLIDERTYPE:")?;
+    for point in &track.points {
+        writeln!(writer, "{}", igc_b_record(point))?;
+    }
+    Ok(())
+}
+
+fn write_kml(track: &Track, name: &str, writer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(writer, r#"<?xml version="1.0" encoding="UTF-8"?>"#)?;
+    writeln!(writer, r#"<kml xmlns="http://www.opengis.net/kml/2.2">"#)?;
+    writeln!(writer, "  <Document>")?;
+    writeln!(writer, "    <name>{}</name>", escape_xml(name))?;
+    writeln!(writer, "    <Placemark>")?;
+    writeln!(writer, "      <name>{}</name>", escape_xml(name))?;
+    writeln!(writer, "      <LineString>")?;
+    writeln!(writer, "        <altitudeMode>absolute</altitudeMode>")?;
+    writeln!(writer, "        <coordinates>")?;
+    for point in &track.points {
+        writeln!(
+            writer,
+            "          {},{},{}",
+            point.lng, point.lat, point.altitude
+        )?;
+    }
+    writeln!(writer, "        </coordinates>")?;
+    writeln!(writer, "      </LineString>")?;
+    writeln!(writer, "    </Placemark>")?;
+    writeln!(writer, "  </Document>")?;
+    writeln!(writer, "</kml>")
+}
+
+fn write_geojson(track: &Track, name: &str, writer: &mut impl Write) -> std::io::Result<()> {
+    let feature = json!({
+        "type": "Feature",
+        "geometry": {
+            "type": "LineString",
+            "coordinates": track
+                .points
+                .iter()
+                .map(|point| json!([point.lng, point.lat, point.altitude]))
+                .collect::<Vec<_>>(),
+        },
+        "properties": {
+            "athleteId": track.athlete_id,
+            "name": name,
+            "timestamps": track.points.iter().map(|point| point.timestamp).collect::<Vec<_>>(),
+        },
+    });
+    serde_json::to_writer_pretty(&mut *writer, &feature)?;
+    writeln!(writer)
+}
+
+fn write_csv(track: &Track, writer: &mut impl Write) -> std::io::Result<()> {
+    writeln!(
+        writer,
+        "timestamp,time,lat,lng,altitude,altitude_agl,status,speed,vertical_speed"
+    )?;
+    for point in &track.points {
+        writeln!(
+            writer,
+            "{},{},{},{},{},{},{},{},{}",
+            point.timestamp,
+            point.time().to_rfc3339(),
+            point.lat,
+            point.lng,
+            point.altitude,
+            point.altitude_agl,
+            point.status.as_deref().unwrap_or(""),
+            point.speed,
+            point.vertical_speed
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::{igc_b_record, Format};
+    use crate::{test_util, ApiTrackResponse, Track, TrackPoint};
+
+    fn track() -> Track {
+        let response: ApiTrackResponse = protobuf::Message::parse_from_bytes(
+            test_util::fixture("/race/athlete/29/track/latest.pbf").unwrap(),
+        )
+        .unwrap();
+        Track::from(response)
+    }
+
+    fn export(format: Format) -> String {
+        let mut out = Vec::new();
+        format
+            .write(&track(), "Christian Maurer", &mut out)
+            .unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn igc_record() {
+        let point = TrackPoint {
+            timestamp: 1686758400.0,
+            lat: 46.805,
+            lng: -8.6,
+            altitude: 1480.4,
+            altitude_agl: 0.0,
+            status: None,
+            speed: 0.0,
+            vertical_speed: 0.0,
+        };
+        assert_eq!(igc_b_record(&point), "B1600004648300N00836000WA0148001480");
+    }
+
+    #[test]
+    fn formats() {
+        assert_eq!(export(Format::Gpx).matches("<trkpt ").count(), 60);
+        assert_eq!(
+            export(Format::Igc)
+                .lines()
+                .filter(|line| line.starts_with('B'))
+                .count(),
+            60
+        );
+        assert!(export(Format::Kml).contains("<coordinates>"));
+        let geojson: serde_json::Value = serde_json::from_str(&export(Format::GeoJson)).unwrap();
+        assert_eq!(
+            geojson["geometry"]["coordinates"].as_array().unwrap().len(),
+            60
+        );
+        assert_eq!(export(Format::Csv).lines().count(), 61);
+        assert_eq!("GeoJSON".parse::<Format>(), Ok(Format::GeoJson));
+    }
+}
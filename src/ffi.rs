@@ -0,0 +1,578 @@
+//! C ABI for embedding in non-Rust apps, declared in `include/xalps.h`.
+//!
+//! Every fetch returns an [`XalpsErrorCode`] and hands out an opaque handle
+//! through its `out` pointer, which must be released with the matching
+//! `*_free` function. Items are read by index; their strings are borrowed
+//! from the handle and valid until it is freed. After an error,
+//! `xalps_last_error_message` describes it.
+//!
+//! Requests block the calling thread, see [`crate::blocking`].
+//!
+//! Regenerate the header with `cbindgen --config cbindgen.toml --output include/xalps.h`.
+
+use crate::{
+    blocking, ApiTrackResponse, AthleteId, AthleteStatus, Error, Overview, RaceStatus, Track,
+    TrackPoint,
+};
+use chrono::{TimeZone, Utc};
+use std::{
+    cell::RefCell,
+    ffi::{c_char, CStr, CString},
+    ptr,
+};
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XalpsErrorCode {
+    Ok = 0,
+    /// A required pointer argument was null.
+    NullArgument = 1,
+    /// A string argument is not valid UTF-8 or not valid for its purpose.
+    InvalidArgument = 2,
+    /// The index is not below the item count.
+    IndexOutOfRange = 3,
+    Http = 10,
+    /// Upstream has no such data, e.g. no track of the athlete.
+    NotFound = 11,
+    Json = 12,
+    Protobuf = 13,
+    NotRecorded = 14,
+    Io = 15,
+    Storage = 16,
+    Server = 17,
+}
+
+impl From<&Error> for XalpsErrorCode {
+    fn from(err: &Error) -> Self {
+        if err.status() == Some(404) {
+            return XalpsErrorCode::NotFound;
+        }
+        match err {
+            Error::Http(_) | Error::Status { .. } => XalpsErrorCode::Http,
+            Error::Json(_) => XalpsErrorCode::Json,
+            Error::Protobuf(_) => XalpsErrorCode::Protobuf,
+            Error::NotRecorded(_) => XalpsErrorCode::NotRecorded,
+            Error::InvalidTrack(_) | Error::InvalidConfig(_) => XalpsErrorCode::InvalidArgument,
+            Error::Io(_) => XalpsErrorCode::Io,
+            #[cfg(feature = "storage")]
+            Error::Storage(_) => XalpsErrorCode::Storage,
+            #[cfg(feature = "server")]
+            Error::Server(_) => XalpsErrorCode::Server,
+        }
+    }
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn fail(code: XalpsErrorCode, message: String) -> XalpsErrorCode {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|last| *last.borrow_mut() = Some(message));
+    code
+}
+
+/// Hands `value` out through `out`, or records the error.
+unsafe fn finish<T>(result: Result<T, Error>, out: *mut *mut T) -> XalpsErrorCode {
+    match result {
+        Ok(value) => {
+            *out = Box::into_raw(Box::new(value));
+            XalpsErrorCode::Ok
+        }
+        Err(err) => fail(XalpsErrorCode::from(&err), err.to_string()),
+    }
+}
+
+unsafe fn str_arg<'a>(s: *const c_char) -> Result<&'a str, XalpsErrorCode> {
+    if s.is_null() {
+        return Err(fail(XalpsErrorCode::NullArgument, "null string".into()));
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map_err(|err| fail(XalpsErrorCode::InvalidArgument, err.to_string()))
+}
+
+unsafe fn athlete_id_arg(s: *const c_char) -> Result<AthleteId, XalpsErrorCode> {
+    let s = str_arg(s)?;
+    s.parse().map_err(|_| {
+        fail(
+            XalpsErrorCode::InvalidArgument,
+            format!("invalid athlete id {}", s),
+        )
+    })
+}
+
+fn c_string(s: &str) -> CString {
+    CString::new(s.replace('\0', "")).unwrap_or_default()
+}
+
+/// Message of the last error on this thread, null if there was none.
+/// Valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn xalps_last_error_message() -> *const c_char {
+    LAST_ERROR.with(|last| {
+        last.borrow()
+            .as_ref()
+            .map_or(ptr::null(), |message| message.as_ptr())
+    })
+}
+
+pub struct XalpsClient(blocking::Client);
+
+/// A client for the live feeds, or for `base_url` serving both hosts if not null.
+///
+/// # Safety
+/// `base_url` is null or a NUL-terminated string, `out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_client_new(
+    base_url: *const c_char,
+    out: *mut *mut XalpsClient,
+) -> XalpsErrorCode {
+    if out.is_null() {
+        return fail(XalpsErrorCode::NullArgument, "null out pointer".into());
+    }
+    let builder = crate::Client::builder();
+    let builder = if base_url.is_null() {
+        builder
+    } else {
+        match str_arg(base_url) {
+            Ok(url) => builder.base_url(url),
+            Err(code) => return code,
+        }
+    };
+    let result = builder
+        .build()
+        .and_then(blocking::Client::new)
+        .map(XalpsClient);
+    finish(result, out)
+}
+
+/// # Safety
+/// `client` is null or was returned by `xalps_client_new` and not freed yet.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_client_free(client: *mut XalpsClient) {
+    if !client.is_null() {
+        drop(Box::from_raw(client));
+    }
+}
+
+/// Strings are owned by the `XalpsOverview` it was read from.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct XalpsAthlete {
+    pub athlete_id: *const c_char,
+    pub firstname: *const c_char,
+    pub lastname: *const c_char,
+    /// ISO 3166-1 alpha-2.
+    pub country_code: *const c_char,
+    pub team: *const c_char,
+    pub hide: bool,
+}
+
+struct AthleteStrings {
+    athlete_id: CString,
+    firstname: CString,
+    lastname: CString,
+    country_code: CString,
+    team: CString,
+    hide: bool,
+}
+
+pub struct XalpsOverview {
+    athletes: Vec<AthleteStrings>,
+}
+
+impl From<Overview> for XalpsOverview {
+    fn from(overview: Overview) -> Self {
+        XalpsOverview {
+            athletes: overview
+                .athletes
+                .iter()
+                .map(|athlete| AthleteStrings {
+                    athlete_id: c_string(&athlete.athlete_id.to_string()),
+                    firstname: c_string(&athlete.firstname),
+                    lastname: c_string(&athlete.lastname),
+                    country_code: c_string(athlete.country_code.alpha2()),
+                    team: c_string(&athlete.team),
+                    hide: athlete.hide,
+                })
+                .collect(),
+        }
+    }
+}
+
+/// # Safety
+/// `client` is a live client, `out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_overview_fetch(
+    client: *const XalpsClient,
+    out: *mut *mut XalpsOverview,
+) -> XalpsErrorCode {
+    if client.is_null() || out.is_null() {
+        return fail(XalpsErrorCode::NullArgument, "null argument".into());
+    }
+    let result = Overview::request_blocking_with(&(*client).0).map(XalpsOverview::from);
+    finish(result, out)
+}
+
+/// # Safety
+/// `overview` is a live overview.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_overview_athlete_count(overview: *const XalpsOverview) -> usize {
+    overview
+        .as_ref()
+        .map_or(0, |overview| overview.athletes.len())
+}
+
+/// # Safety
+/// `overview` is a live overview, `out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_overview_athlete(
+    overview: *const XalpsOverview,
+    index: usize,
+    out: *mut XalpsAthlete,
+) -> XalpsErrorCode {
+    let (Some(overview), false) = (overview.as_ref(), out.is_null()) else {
+        return fail(XalpsErrorCode::NullArgument, "null argument".into());
+    };
+    let Some(athlete) = overview.athletes.get(index) else {
+        return fail(
+            XalpsErrorCode::IndexOutOfRange,
+            format!("no athlete {} of {}", index, overview.athletes.len()),
+        );
+    };
+    *out = XalpsAthlete {
+        athlete_id: athlete.athlete_id.as_ptr(),
+        firstname: athlete.firstname.as_ptr(),
+        lastname: athlete.lastname.as_ptr(),
+        country_code: athlete.country_code.as_ptr(),
+        team: athlete.team.as_ptr(),
+        hide: athlete.hide,
+    };
+    XalpsErrorCode::Ok
+}
+
+/// # Safety
+/// `overview` is null or a live overview.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_overview_free(overview: *mut XalpsOverview) {
+    if !overview.is_null() {
+        drop(Box::from_raw(overview));
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XalpsAthleteStatus {
+    Rest,
+    Fly,
+    Hike,
+}
+
+impl From<AthleteStatus> for XalpsAthleteStatus {
+    fn from(status: AthleteStatus) -> Self {
+        match status {
+            AthleteStatus::Rest => XalpsAthleteStatus::Rest,
+            AthleteStatus::Fly => XalpsAthleteStatus::Fly,
+            AthleteStatus::Hike => XalpsAthleteStatus::Hike,
+        }
+    }
+}
+
+/// `athlete_id` is owned by the `XalpsRaceStatusList` it was read from.
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct XalpsRaceStatus {
+    pub athlete_id: *const c_char,
+    /// Unix timestamp in seconds.
+    pub timestamp: i64,
+    pub status: XalpsAthleteStatus,
+    /// Kilometres.
+    pub distance_to_goal: f64,
+    /// Metres above sea level.
+    pub altitude: u32,
+}
+
+pub struct XalpsRaceStatusList {
+    status: Vec<(CString, RaceStatus)>,
+}
+
+/// # Safety
+/// `client` is a live client, `out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_race_status_fetch(
+    client: *const XalpsClient,
+    out: *mut *mut XalpsRaceStatusList,
+) -> XalpsErrorCode {
+    if client.is_null() || out.is_null() {
+        return fail(XalpsErrorCode::NullArgument, "null argument".into());
+    }
+    let result =
+        RaceStatus::request_blocking_with(&(*client).0).map(|status| XalpsRaceStatusList {
+            status: status
+                .into_iter()
+                .map(|status| (c_string(&status.athlete_id.to_string()), status))
+                .collect(),
+        });
+    finish(result, out)
+}
+
+/// # Safety
+/// `list` is a live race status list.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_race_status_count(list: *const XalpsRaceStatusList) -> usize {
+    list.as_ref().map_or(0, |list| list.status.len())
+}
+
+/// # Safety
+/// `list` is a live race status list, `out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_race_status_get(
+    list: *const XalpsRaceStatusList,
+    index: usize,
+    out: *mut XalpsRaceStatus,
+) -> XalpsErrorCode {
+    let (Some(list), false) = (list.as_ref(), out.is_null()) else {
+        return fail(XalpsErrorCode::NullArgument, "null argument".into());
+    };
+    let Some((athlete_id, status)) = list.status.get(index) else {
+        return fail(
+            XalpsErrorCode::IndexOutOfRange,
+            format!("no race status {} of {}", index, list.status.len()),
+        );
+    };
+    *out = XalpsRaceStatus {
+        athlete_id: athlete_id.as_ptr(),
+        timestamp: status.timestamp as i64,
+        status: status.status.into(),
+        distance_to_goal: status.distance_to_goal,
+        altitude: status.altitude as u32,
+    };
+    XalpsErrorCode::Ok
+}
+
+/// # Safety
+/// `list` is null or a live race status list.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_race_status_free(list: *mut XalpsRaceStatusList) {
+    if !list.is_null() {
+        drop(Box::from_raw(list));
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum XalpsTrackKind {
+    Latest,
+    Reduced,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct XalpsTrackPoint {
+    /// Unix timestamp in seconds.
+    pub timestamp: f64,
+    pub lat: f32,
+    pub lng: f32,
+    pub altitude: f32,
+    pub altitude_agl: f32,
+    pub speed: f32,
+    pub vertical_speed: f32,
+}
+
+impl From<&TrackPoint> for XalpsTrackPoint {
+    fn from(point: &TrackPoint) -> Self {
+        XalpsTrackPoint {
+            timestamp: point.timestamp,
+            lat: point.lat,
+            lng: point.lng,
+            altitude: point.altitude,
+            altitude_agl: point.altitude_agl,
+            speed: point.speed,
+            vertical_speed: point.vertical_speed,
+        }
+    }
+}
+
+pub struct XalpsTrack(Track);
+
+/// # Safety
+/// `client` is a live client, `athlete_id` a NUL-terminated string, `out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_track_fetch(
+    client: *const XalpsClient,
+    athlete_id: *const c_char,
+    kind: XalpsTrackKind,
+    out: *mut *mut XalpsTrack,
+) -> XalpsErrorCode {
+    if client.is_null() || out.is_null() {
+        return fail(XalpsErrorCode::NullArgument, "null argument".into());
+    }
+    let athlete_id = match athlete_id_arg(athlete_id) {
+        Ok(athlete_id) => athlete_id,
+        Err(code) => return code,
+    };
+    let client = &(*client).0;
+    let result = match kind {
+        XalpsTrackKind::Latest => ApiTrackResponse::request_blocking_with(client, athlete_id),
+        XalpsTrackKind::Reduced => {
+            ApiTrackResponse::request_reduced_blocking_with(client, athlete_id)
+        }
+    };
+    finish(result.map(|response| XalpsTrack(response.into())), out)
+}
+
+/// The track as it was at `time`, in Unix seconds on a 5 minute boundary.
+///
+/// # Safety
+/// `client` is a live client, `athlete_id` a NUL-terminated string, `out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_track_fetch_replay(
+    client: *const XalpsClient,
+    athlete_id: *const c_char,
+    time: i64,
+    out: *mut *mut XalpsTrack,
+) -> XalpsErrorCode {
+    if client.is_null() || out.is_null() {
+        return fail(XalpsErrorCode::NullArgument, "null argument".into());
+    }
+    let athlete_id = match athlete_id_arg(athlete_id) {
+        Ok(athlete_id) => athlete_id,
+        Err(code) => return code,
+    };
+    let Some(time) = Utc.timestamp_opt(time, 0).single() else {
+        return fail(XalpsErrorCode::InvalidArgument, "invalid time".into());
+    };
+    let result = ApiTrackResponse::request_replay_blocking_with(&(*client).0, athlete_id, time);
+    finish(result.map(|response| XalpsTrack(response.into())), out)
+}
+
+/// # Safety
+/// `track` is a live track.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_track_athlete_id(track: *const XalpsTrack) -> i32 {
+    track.as_ref().map_or(0, |track| track.0.athlete_id.get())
+}
+
+/// # Safety
+/// `track` is a live track.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_track_point_count(track: *const XalpsTrack) -> usize {
+    track.as_ref().map_or(0, |track| track.0.points.len())
+}
+
+/// # Safety
+/// `track` is a live track, `out` is valid for writes.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_track_point(
+    track: *const XalpsTrack,
+    index: usize,
+    out: *mut XalpsTrackPoint,
+) -> XalpsErrorCode {
+    let (Some(track), false) = (track.as_ref(), out.is_null()) else {
+        return fail(XalpsErrorCode::NullArgument, "null argument".into());
+    };
+    let Some(point) = track.0.points.get(index) else {
+        return fail(
+            XalpsErrorCode::IndexOutOfRange,
+            format!("no track point {} of {}", index, track.0.points.len()),
+        );
+    };
+    *out = point.into();
+    XalpsErrorCode::Ok
+}
+
+/// # Safety
+/// `track` is null or a live track.
+#[no_mangle]
+pub unsafe extern "C" fn xalps_track_free(track: *mut XalpsTrack) {
+    if !track.is_null() {
+        drop(Box::from_raw(track));
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::test_util::MockServer;
+    use std::mem::MaybeUninit;
+
+    #[test]
+    fn fetch_iterate_free() {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let server = runtime.block_on(MockServer::start());
+        let url = CString::new(server.url()).unwrap();
+        unsafe {
+            let mut client = ptr::null_mut();
+            assert_eq!(
+                xalps_client_new(url.as_ptr(), &mut client),
+                XalpsErrorCode::Ok
+            );
+
+            let mut overview = ptr::null_mut();
+            assert_eq!(
+                xalps_overview_fetch(client, &mut overview),
+                XalpsErrorCode::Ok
+            );
+            let count = xalps_overview_athlete_count(overview);
+            assert!(count > 0);
+            let mut athlete = MaybeUninit::uninit();
+            assert_eq!(
+                xalps_overview_athlete(overview, 0, athlete.as_mut_ptr()),
+                XalpsErrorCode::Ok
+            );
+            assert!(!CStr::from_ptr(athlete.assume_init().lastname)
+                .to_bytes()
+                .is_empty());
+            assert_eq!(
+                xalps_overview_athlete(overview, count, athlete.as_mut_ptr()),
+                XalpsErrorCode::IndexOutOfRange
+            );
+            xalps_overview_free(overview);
+
+            let mut status = ptr::null_mut();
+            assert_eq!(
+                xalps_race_status_fetch(client, &mut status),
+                XalpsErrorCode::Ok
+            );
+            assert_eq!(xalps_race_status_count(status), 3);
+            let mut first = MaybeUninit::uninit();
+            xalps_race_status_get(status, 0, first.as_mut_ptr());
+            let first = first.assume_init();
+            assert_eq!(CStr::from_ptr(first.athlete_id).to_str(), Ok("29"));
+            assert_eq!(first.status, XalpsAthleteStatus::Fly);
+            xalps_race_status_free(status);
+
+            let mut track = ptr::null_mut();
+            let athlete_id = CString::new("29").unwrap();
+            assert_eq!(
+                xalps_track_fetch(
+                    client,
+                    athlete_id.as_ptr(),
+                    XalpsTrackKind::Reduced,
+                    &mut track
+                ),
+                XalpsErrorCode::Ok
+            );
+            assert_eq!(xalps_track_athlete_id(track), 29);
+            assert_eq!(xalps_track_point_count(track), 12);
+            let mut point = MaybeUninit::uninit();
+            assert_eq!(
+                xalps_track_point(track, 11, point.as_mut_ptr()),
+                XalpsErrorCode::Ok
+            );
+            assert!(point.assume_init().timestamp > 1.6e9);
+            xalps_track_free(track);
+
+            let missing = CString::new("3").unwrap();
+            let mut track = ptr::null_mut();
+            assert_eq!(
+                xalps_track_fetch(client, missing.as_ptr(), XalpsTrackKind::Latest, &mut track),
+                XalpsErrorCode::NotFound
+            );
+            assert!(track.is_null());
+            assert!(!xalps_last_error_message().is_null());
+            xalps_client_free(client);
+        }
+    }
+}
@@ -0,0 +1,481 @@
+//! Distances, bearings and projections of positions, in `f64` whatever the
+//! precision of the source, so every analysis agrees on them.
+//!
+//! Spherical functions use [`EARTH_RADIUS`]; [`LatLng::vincenty_distance`],
+//! [`LocalFrame`] and [`Utm`] use the WGS 84 ellipsoid.
+
+use crate::{ApiTrackPoint, TrackPoint, TurnPoint};
+use serde::{Deserialize, Serialize};
+
+/// Mean radius of the earth in metres.
+pub const EARTH_RADIUS: f64 = 6_371_008.8;
+
+/// Semi-major axis of the WGS 84 ellipsoid in metres.
+const WGS84_A: f64 = 6_378_137.0;
+/// Flattening of the WGS 84 ellipsoid.
+const WGS84_F: f64 = 1.0 / 298.257_223_563;
+/// Square of the first eccentricity.
+const WGS84_E2: f64 = WGS84_F * (2.0 - WGS84_F);
+
+/// A position in degrees.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct LatLng {
+    pub lat: f64,
+    pub lng: f64,
+}
+
+impl LatLng {
+    pub const fn new(lat: f64, lng: f64) -> LatLng {
+        LatLng { lat, lng }
+    }
+
+    pub(crate) fn is_valid(self) -> bool {
+        (-90.0..=90.0).contains(&self.lat) && (-180.0..=180.0).contains(&self.lng)
+    }
+
+    /// Great circle distance in metres.
+    pub fn haversine_distance(self, other: LatLng) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lng = (other.lng - self.lng).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lng / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS * a.sqrt().min(1.0).asin()
+    }
+
+    /// Distance in metres on the WGS 84 ellipsoid, accurate to a millimetre.
+    /// `None` for nearly antipodal points, where the iteration doesn't converge.
+    pub fn vincenty_distance(self, other: LatLng) -> Option<f64> {
+        let b = WGS84_A * (1.0 - WGS84_F);
+        let l = (other.lng - self.lng).to_radians();
+        let u1 = ((1.0 - WGS84_F) * self.lat.to_radians().tan()).atan();
+        let u2 = ((1.0 - WGS84_F) * other.lat.to_radians().tan()).atan();
+        let (sin_u1, cos_u1) = u1.sin_cos();
+        let (sin_u2, cos_u2) = u2.sin_cos();
+
+        let mut lambda = l;
+        for _ in 0..200 {
+            let (sin_lambda, cos_lambda) = lambda.sin_cos();
+            let sin_sigma =
+                (cos_u2 * sin_lambda).hypot(cos_u1 * sin_u2 - sin_u1 * cos_u2 * cos_lambda);
+            if sin_sigma == 0.0 {
+                return Some(0.0);
+            }
+            let cos_sigma = sin_u1 * sin_u2 + cos_u1 * cos_u2 * cos_lambda;
+            let sigma = sin_sigma.atan2(cos_sigma);
+            let sin_alpha = cos_u1 * cos_u2 * sin_lambda / sin_sigma;
+            let cos2_alpha = 1.0 - sin_alpha * sin_alpha;
+            // On the equator cos²α is 0 and so is the term.
+            let cos_2sigma_m = if cos2_alpha == 0.0 {
+                0.0
+            } else {
+                cos_sigma - 2.0 * sin_u1 * sin_u2 / cos2_alpha
+            };
+            let c = WGS84_F / 16.0 * cos2_alpha * (4.0 + WGS84_F * (4.0 - 3.0 * cos2_alpha));
+            let previous = lambda;
+            lambda = l
+                + (1.0 - c)
+                    * WGS84_F
+                    * sin_alpha
+                    * (sigma
+                        + c * sin_sigma
+                            * (cos_2sigma_m
+                                + c * cos_sigma * (-1.0 + 2.0 * cos_2sigma_m * cos_2sigma_m)));
+            if (lambda - previous).abs() < 1e-12 {
+                let u2 = cos2_alpha * (WGS84_A * WGS84_A - b * b) / (b * b);
+                let a = 1.0 + u2 / 16384.0 * (4096.0 + u2 * (-768.0 + u2 * (320.0 - 175.0 * u2)));
+                let b_ = u2 / 1024.0 * (256.0 + u2 * (-128.0 + u2 * (74.0 - 47.0 * u2)));
+                let delta_sigma = b_
+                    * sin_sigma
+                    * (cos_2sigma_m
+                        + b_ / 4.0
+                            * (cos_sigma * (-1.0 + 2.0 * cos_2sigma_m * cos_2sigma_m)
+                                - b_ / 6.0
+                                    * cos_2sigma_m
+                                    * (-3.0 + 4.0 * sin_sigma * sin_sigma)
+                                    * (-3.0 + 4.0 * cos_2sigma_m * cos_2sigma_m)));
+                return Some(b * a * (sigma - delta_sigma));
+            }
+        }
+        None
+    }
+
+    /// Initial great circle bearing towards `other`, in degrees clockwise from
+    /// north in `[0, 360)`.
+    pub fn initial_bearing(self, other: LatLng) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let d_lng = (other.lng - self.lng).to_radians();
+        let y = d_lng.sin() * lat2.cos();
+        let x = lat1.cos() * lat2.sin() - lat1.sin() * lat2.cos() * d_lng.cos();
+        y.atan2(x).to_degrees().rem_euclid(360.0)
+    }
+
+    /// The position `distance` metres away along the great circle starting at
+    /// `bearing` degrees.
+    pub fn destination(self, bearing: f64, distance: f64) -> LatLng {
+        let lat1 = self.lat.to_radians();
+        let bearing = bearing.to_radians();
+        let angle = distance / EARTH_RADIUS;
+        let lat2 = (lat1.sin() * angle.cos() + lat1.cos() * angle.sin() * bearing.cos()).asin();
+        let lng2 = self.lng.to_radians()
+            + (bearing.sin() * angle.sin() * lat1.cos())
+                .atan2(angle.cos() - lat1.sin() * lat2.sin());
+        LatLng::new(
+            lat2.to_degrees(),
+            (lng2.to_degrees() + 540.0).rem_euclid(360.0) - 180.0,
+        )
+    }
+}
+
+impl From<&TrackPoint> for LatLng {
+    fn from(point: &TrackPoint) -> Self {
+        LatLng::new(point.lat.into(), point.lng.into())
+    }
+}
+
+impl From<&ApiTrackPoint> for LatLng {
+    fn from(point: &ApiTrackPoint) -> Self {
+        LatLng::new(point.get_lat().into(), point.get_lng().into())
+    }
+}
+
+impl From<&TurnPoint> for LatLng {
+    fn from(turnpoint: &TurnPoint) -> Self {
+        LatLng::new(turnpoint.lat, turnpoint.lng)
+    }
+}
+
+/// Metres east, north and up of the origin of a [`LocalFrame`].
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Enu {
+    pub east: f64,
+    pub north: f64,
+    pub up: f64,
+}
+
+/// Earth-centred, earth-fixed coordinates in metres.
+fn to_ecef(position: LatLng, altitude: f64) -> [f64; 3] {
+    let (sin_lat, cos_lat) = position.lat.to_radians().sin_cos();
+    let (sin_lng, cos_lng) = position.lng.to_radians().sin_cos();
+    let n = WGS84_A / (1.0 - WGS84_E2 * sin_lat * sin_lat).sqrt();
+    [
+        (n + altitude) * cos_lat * cos_lng,
+        (n + altitude) * cos_lat * sin_lng,
+        (n * (1.0 - WGS84_E2) + altitude) * sin_lat,
+    ]
+}
+
+fn from_ecef([x, y, z]: [f64; 3]) -> (LatLng, f64) {
+    let p = x.hypot(y);
+    let mut lat = z.atan2(p * (1.0 - WGS84_E2));
+    let mut altitude = 0.0;
+    for _ in 0..5 {
+        let sin_lat = lat.sin();
+        let n = WGS84_A / (1.0 - WGS84_E2 * sin_lat * sin_lat).sqrt();
+        altitude = p / lat.cos() - n;
+        lat = z.atan2(p * (1.0 - WGS84_E2 * n / (n + altitude)));
+    }
+    (
+        LatLng::new(lat.to_degrees(), y.atan2(x).to_degrees()),
+        altitude,
+    )
+}
+
+/// A tangent plane at a position, e.g. the launch, for flat-earth maths
+/// around it. Altitudes are above the ellipsoid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LocalFrame {
+    origin: LatLng,
+    ecef: [f64; 3],
+}
+
+impl LocalFrame {
+    pub fn new(origin: LatLng, altitude: f64) -> LocalFrame {
+        LocalFrame {
+            origin,
+            ecef: to_ecef(origin, altitude),
+        }
+    }
+
+    pub fn origin(&self) -> LatLng {
+        self.origin
+    }
+
+    pub fn to_enu(&self, position: LatLng, altitude: f64) -> Enu {
+        let [x, y, z] = to_ecef(position, altitude);
+        let (dx, dy, dz) = (x - self.ecef[0], y - self.ecef[1], z - self.ecef[2]);
+        let (sin_lat, cos_lat) = self.origin.lat.to_radians().sin_cos();
+        let (sin_lng, cos_lng) = self.origin.lng.to_radians().sin_cos();
+        Enu {
+            east: -sin_lng * dx + cos_lng * dy,
+            north: -sin_lat * cos_lng * dx - sin_lat * sin_lng * dy + cos_lat * dz,
+            up: cos_lat * cos_lng * dx + cos_lat * sin_lng * dy + sin_lat * dz,
+        }
+    }
+
+    /// The position and altitude of a point of the frame.
+    pub fn from_enu(&self, enu: Enu) -> (LatLng, f64) {
+        let (sin_lat, cos_lat) = self.origin.lat.to_radians().sin_cos();
+        let (sin_lng, cos_lng) = self.origin.lng.to_radians().sin_cos();
+        let Enu { east, north, up } = enu;
+        from_ecef([
+            self.ecef[0] - sin_lng * east - sin_lat * cos_lng * north + cos_lat * cos_lng * up,
+            self.ecef[1] + cos_lng * east - sin_lat * sin_lng * north + cos_lat * sin_lng * up,
+            self.ecef[2] + cos_lat * north + sin_lat * up,
+        ])
+    }
+}
+
+/// Universal Transverse Mercator coordinates on WGS 84, in metres.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct Utm {
+    pub zone: u8,
+    pub northern: bool,
+    pub easting: f64,
+    pub northing: f64,
+}
+
+const UTM_SCALE: f64 = 0.9996;
+const UTM_FALSE_EASTING: f64 = 500_000.0;
+const UTM_FALSE_NORTHING: f64 = 10_000_000.0;
+
+/// Coefficients of Krüger's series to the third order of the third flattening.
+struct Kruger {
+    /// Radius of the rectifying sphere, scaled.
+    radius: f64,
+    alpha: [f64; 3],
+    beta: [f64; 3],
+    delta: [f64; 3],
+}
+
+fn kruger() -> Kruger {
+    let n = WGS84_F / (2.0 - WGS84_F);
+    let (n2, n3) = (n * n, n * n * n);
+    Kruger {
+        radius: UTM_SCALE * WGS84_A / (1.0 + n) * (1.0 + n2 / 4.0 + n2 * n2 / 64.0),
+        alpha: [
+            n / 2.0 - 2.0 * n2 / 3.0 + 5.0 * n3 / 16.0,
+            13.0 * n2 / 48.0 - 3.0 * n3 / 5.0,
+            61.0 * n3 / 240.0,
+        ],
+        beta: [
+            n / 2.0 - 2.0 * n2 / 3.0 + 37.0 * n3 / 96.0,
+            n2 / 48.0 + n3 / 15.0,
+            17.0 * n3 / 480.0,
+        ],
+        delta: [
+            2.0 * n - 2.0 * n2 / 3.0 - 2.0 * n3,
+            7.0 * n2 / 3.0 - 8.0 * n3 / 5.0,
+            56.0 * n3 / 15.0,
+        ],
+    }
+}
+
+fn central_meridian(zone: u8) -> f64 {
+    f64::from(zone) * 6.0 - 183.0
+}
+
+impl Utm {
+    /// The standard zone of the position, with the exceptions around Norway
+    /// and Svalbard.
+    pub fn zone(position: LatLng) -> u8 {
+        let LatLng { lat, lng } = position;
+        if (56.0..64.0).contains(&lat) && (3.0..12.0).contains(&lng) {
+            return 32;
+        }
+        if (72.0..=84.0).contains(&lat) && (0.0..42.0).contains(&lng) {
+            return match lng {
+                lng if lng < 9.0 => 31,
+                lng if lng < 21.0 => 33,
+                lng if lng < 33.0 => 35,
+                _ => 37,
+            };
+        }
+        ((lng + 180.0).rem_euclid(360.0) / 6.0) as u8 % 60 + 1
+    }
+
+    /// Projects the position in its standard zone. UTM is only defined from
+    /// 80° S to 84° N.
+    pub fn from_lat_lng(position: LatLng) -> Utm {
+        Utm::project(position, Utm::zone(position))
+    }
+
+    /// Projects the position in the given zone, e.g. to keep a track that
+    /// crosses zones on one grid. Accurate to a millimetre within 3000 km of
+    /// the zone's central meridian. `None` if the zone isn't 1 to 60.
+    pub fn in_zone(position: LatLng, zone: u8) -> Option<Utm> {
+        (1..=60)
+            .contains(&zone)
+            .then(|| Utm::project(position, zone))
+    }
+
+    fn project(position: LatLng, zone: u8) -> Utm {
+        let k = kruger();
+        let lat = position.lat.to_radians();
+        let d_lng = (position.lng - central_meridian(zone)).to_radians();
+        let e = WGS84_E2.sqrt();
+        let t = (lat.sin().atanh() - e * (e * lat.sin()).atanh()).sinh();
+        let xi = t.atan2(d_lng.cos());
+        let eta = (d_lng.sin() / (1.0 + t * t).sqrt()).atanh();
+        let (mut easting, mut northing) = (eta, xi);
+        for (j, alpha) in k.alpha.iter().enumerate() {
+            let j = 2.0 * (j + 1) as f64;
+            easting += alpha * (j * xi).cos() * (j * eta).sinh();
+            northing += alpha * (j * xi).sin() * (j * eta).cosh();
+        }
+        let northern = position.lat >= 0.0;
+        Utm {
+            zone,
+            northern,
+            easting: UTM_FALSE_EASTING + k.radius * easting,
+            northing: k.radius * northing + if northern { 0.0 } else { UTM_FALSE_NORTHING },
+        }
+    }
+
+    pub fn to_lat_lng(&self) -> LatLng {
+        let k = kruger();
+        let false_northing = if self.northern {
+            0.0
+        } else {
+            UTM_FALSE_NORTHING
+        };
+        let xi = (self.northing - false_northing) / k.radius;
+        let eta = (self.easting - UTM_FALSE_EASTING) / k.radius;
+        let (mut xi_, mut eta_) = (xi, eta);
+        for (j, beta) in k.beta.iter().enumerate() {
+            let j = 2.0 * (j + 1) as f64;
+            xi_ -= beta * (j * xi).sin() * (j * eta).cosh();
+            eta_ -= beta * (j * xi).cos() * (j * eta).sinh();
+        }
+        let chi = (xi_.sin() / eta_.cosh()).asin();
+        let mut lat = chi;
+        for (j, delta) in k.delta.iter().enumerate() {
+            lat += delta * (2.0 * (j + 1) as f64 * chi).sin();
+        }
+        LatLng::new(
+            lat.to_degrees(),
+            central_meridian(self.zone) + eta_.sinh().atan2(xi_.cos()).to_degrees(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LatLng, LocalFrame, Utm};
+
+    fn dms(degrees: f64, minutes: f64, seconds: f64) -> f64 {
+        degrees.signum() * (degrees.abs() + minutes / 60.0 + seconds / 3600.0)
+    }
+
+    /// Flinders Peak and Buninyong, the worked example of Vincenty's paper
+    /// as published by Geoscience Australia.
+    fn flinders_peak() -> LatLng {
+        LatLng::new(dms(-37.0, 57.0, 3.72030), dms(144.0, 25.0, 29.52440))
+    }
+
+    fn buninyong() -> LatLng {
+        LatLng::new(dms(-37.0, 39.0, 10.15610), dms(143.0, 55.0, 35.38390))
+    }
+
+    #[test]
+    fn distances() {
+        let vincenty = flinders_peak().vincenty_distance(buninyong()).unwrap();
+        assert!((vincenty - 54_972.271).abs() < 0.001, "{}", vincenty);
+        let haversine = flinders_peak().haversine_distance(buninyong());
+        assert!((haversine / vincenty - 1.0).abs() < 0.005, "{}", haversine);
+
+        let point = LatLng::new(46.5, 8.0);
+        assert_eq!(point.haversine_distance(point), 0.0);
+        assert_eq!(point.vincenty_distance(point), Some(0.0));
+        // A degree along the equator.
+        let equator = LatLng::new(0.0, 0.0).vincenty_distance(LatLng::new(0.0, 1.0));
+        assert!((equator.unwrap() - 111_319.491).abs() < 0.001);
+        assert_eq!(
+            LatLng::new(0.0, 0.0).vincenty_distance(LatLng::new(0.5, 179.7)),
+            None
+        );
+    }
+
+    #[test]
+    fn bearings() {
+        let north = LatLng::new(46.0, 8.0);
+        assert!((north.initial_bearing(LatLng::new(47.0, 8.0))).abs() < 1e-9);
+        assert!((north.initial_bearing(LatLng::new(46.0, 7.0)) - 270.36).abs() < 0.01);
+        // The ellipsoidal azimuth of the example is 306°52'05.37".
+        let bearing = flinders_peak().initial_bearing(buninyong());
+        assert!(
+            (bearing - dms(306.0, 52.0, 5.37)).abs() < 0.2,
+            "{}",
+            bearing
+        );
+
+        let start = flinders_peak();
+        let end = buninyong();
+        let destination =
+            start.destination(start.initial_bearing(end), start.haversine_distance(end));
+        assert!(destination.haversine_distance(end) < 0.001);
+        let across = LatLng::new(0.0, 179.9).destination(90.0, 22_239.0);
+        assert!((across.lng + 179.9).abs() < 1e-3, "{:?}", across);
+    }
+
+    #[test]
+    fn local_frame() {
+        let launch = LatLng::new(47.79871, 13.04396);
+        let frame = LocalFrame::new(launch, 500.0);
+        let origin = frame.to_enu(launch, 500.0);
+        assert!(origin.east.abs() < 1e-6 && origin.north.abs() < 1e-6 && origin.up.abs() < 1e-6);
+
+        let north = launch.destination(0.0, 1000.0);
+        let enu = frame.to_enu(north, 500.0);
+        assert!(enu.east.abs() < 1e-6);
+        assert!((enu.north - 1000.0).abs() < 5.0, "{:?}", enu);
+        // The surface drops away below the tangent plane.
+        assert!((enu.up + 0.08).abs() < 0.01, "{:?}", enu);
+
+        let point = LatLng::new(47.9, 13.2);
+        let (back, altitude) = frame.from_enu(frame.to_enu(point, 1234.5));
+        assert!(back.haversine_distance(point) < 1e-6);
+        assert!((altitude - 1234.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn utm() {
+        // Geoscience Australia's worked example, on GRS 80 which differs from
+        // WGS 84 by less than a millimetre here.
+        let utm = Utm::from_lat_lng(flinders_peak());
+        assert_eq!((utm.zone, utm.northern), (55, false));
+        assert!((utm.easting - 273_741.297).abs() < 0.01, "{:?}", utm);
+        assert!((utm.northing - 5_796_489.777).abs() < 0.01, "{:?}", utm);
+
+        // The CN Tower, 17T 630084 4833439.
+        let cn_tower =
+            Utm::from_lat_lng(LatLng::new(dms(43.0, 38.0, 33.24), dms(-79.0, 23.0, 13.7)));
+        assert_eq!((cn_tower.zone, cn_tower.northern), (17, true));
+        assert!((cn_tower.easting - 630_084.0).abs() < 1.0, "{:?}", cn_tower);
+        assert!(
+            (cn_tower.northing - 4_833_439.0).abs() < 1.0,
+            "{:?}",
+            cn_tower
+        );
+
+        let back = utm.to_lat_lng();
+        assert!(
+            back.haversine_distance(flinders_peak()) < 0.001,
+            "{:?}",
+            back
+        );
+        // Salzburg lies in zone 33, Monaco in 32; one grid for the whole race.
+        for position in [
+            LatLng::new(47.79871, 13.04396),
+            LatLng::new(43.73484, 7.42062),
+        ] {
+            let back = Utm::in_zone(position, 32).unwrap().to_lat_lng();
+            assert!(back.haversine_distance(position) < 0.001, "{:?}", back);
+        }
+        assert_eq!(Utm::in_zone(LatLng::new(47.0, 13.0), 0), None);
+        assert_eq!(Utm::in_zone(LatLng::new(47.0, 13.0), 61), None);
+        assert_eq!(Utm::zone(LatLng::new(60.0, 5.0)), 32);
+        assert_eq!(Utm::zone(LatLng::new(78.0, 10.0)), 33);
+        assert_eq!(Utm::zone(LatLng::new(0.0, 179.9)), 60);
+        assert_eq!(Utm::zone(LatLng::new(0.0, -180.0)), 1);
+    }
+}
@@ -1,3 +1,48 @@
+#[cfg(feature = "storage")]
+pub mod archive;
+mod athlete_index;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod client;
+mod error;
+pub mod events;
+pub mod export;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+pub mod geo;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod notify;
+#[cfg(feature = "python")]
+mod python;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(feature = "server")]
+pub mod server;
+mod simplify;
 mod status;
+#[cfg(feature = "storage")]
+pub mod storage;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_util;
+mod track;
+mod turnpoint;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-pub use status::{ApiTrackResponse, Overview, RaceStatus, RaceStatusReplay};
+pub use athlete_index::{AthleteIndex, FindError};
+pub use client::{
+    endpoint, Cassette, Client, ClientBuilder, FeedObserver, RateLimit, RateLimiter, Recording,
+    RequestPolicy, DATA_URL, WEBSITE_URL,
+};
+pub use error::Error;
+pub use geo::LatLng;
+pub use status::{
+    ApiTrackPoint, ApiTrackResponse, Athlete, AthleteId, AthleteStatus, DateRange, HexColor, News,
+    Overview, ParseAthleteIdError, ParseColorError, RaceStatus, RaceStatusReplay, Sponsor,
+    ThreeDimensionalModels, TurnPoint, TurnPointId,
+};
+pub use track::{DecodeReport, PointIssue, Repair, Track, TrackPoint, UnknownField};
+pub use turnpoint::{BoundingBox, ParsePolygonError, Polygon, TurnPointGeometry};
+pub use url::Url;
@@ -1,13 +1,278 @@
-use xalps::Overview;
+mod cli;
+
+use chrono::{DateTime, NaiveDate, Utc};
+use clap::{Args, Parser, Subcommand};
+use cli::TrackSource;
+#[cfg(feature = "server")]
+use std::{net::SocketAddr, sync::Arc};
+use std::{path::PathBuf, time::Duration};
+#[cfg(all(feature = "server", feature = "metrics"))]
+use xalps::metrics::Metrics;
+#[cfg(feature = "server")]
+use xalps::server::{self, Proxy, ProxyConfig};
+use xalps::{
+    archive::{ArchiveConfig, ArchiveEvent, Archiver},
+    export::Format,
+    notify::{Notifier, NotifierConfig},
+    storage::Storage,
+    Cassette, Client, Overview,
+};
+
+#[derive(Parser)]
+#[command(name = "xalps", version, about = "Red Bull X-Alps live tracking")]
+struct Cli {
+    /// Print the feeds as JSON instead of tables
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(flatten)]
+    cassette: CassetteArgs,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// List the athletes and turnpoints
+    Overview,
+    /// Show the current standings
+    Status,
+    /// Show the track of an athlete, given by id, lt_slug or name
+    Track {
+        athlete: String,
+        #[command(flatten)]
+        source: TrackArgs,
+    },
+    /// Show the race status snapshots of a day (YYYY-MM-DD)
+    Replay { date: NaiveDate },
+    /// Write the track of an athlete, given by id, lt_slug or name, to a file
+    Export {
+        athlete: String,
+        /// One of gpx, igc, kml, geojson or csv
+        #[arg(long, short)]
+        format: Format,
+        /// Output file, stdout if missing
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+        #[command(flatten)]
+        source: TrackArgs,
+    },
+    /// Compare the JSON feeds with the models and report added, removed or retyped fields
+    SchemaCheck,
+    /// Watch a live leaderboard in the terminal
+    #[cfg(feature = "tui")]
+    Tui {
+        /// Seconds between polls of the race status
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+    /// Serve the feeds as cached JSON over HTTP
+    #[cfg(feature = "server")]
+    Serve {
+        /// Address to listen on
+        #[arg(long, default_value = "127.0.0.1:8080")]
+        listen: SocketAddr,
+        /// Seconds the race status is cached
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+        status_ttl: u64,
+        /// Seconds every athlete's track is cached
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+        track_ttl: u64,
+        /// Seconds between polls for updates pushed to subscribers
+        #[arg(long, default_value_t = 30, value_parser = clap::value_parser!(u64).range(1..))]
+        push_interval: u64,
+    },
+    /// Post race events to the webhooks of a JSON config file until stopped
+    Notify {
+        /// Config file with the webhooks, see the readme
+        #[arg(long, default_value = "webhooks.json")]
+        config: PathBuf,
+        /// Seconds between race status polls
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+        interval: u64,
+    },
+    /// Snapshot the whole race into a local SQLite database until stopped
+    Archive {
+        /// Database file, created if missing and resumed from otherwise
+        #[arg(long, default_value = "xalps.sqlite")]
+        database: PathBuf,
+        /// Seconds between race status snapshots
+        #[arg(long, default_value_t = 60, value_parser = clap::value_parser!(u64).range(1..))]
+        status_interval: u64,
+        /// Seconds between track snapshots of every athlete
+        #[arg(long, default_value_t = 300, value_parser = clap::value_parser!(u64).range(1..))]
+        track_interval: u64,
+        /// Log gaps between track points longer than this many seconds
+        #[arg(long, default_value_t = 600)]
+        gap_threshold: u64,
+    },
+}
+
+#[derive(Args)]
+#[group(multiple = false)]
+struct CassetteArgs {
+    /// Record every response of the feeds into this directory
+    #[arg(long, global = true, value_name = "DIR")]
+    record: Option<PathBuf>,
+    /// Answer from the responses recorded into this directory instead of the feeds
+    #[arg(long, global = true, value_name = "DIR")]
+    replay: Option<PathBuf>,
+}
+
+impl CassetteArgs {
+    /// Makes the shared client record into or replay from the directory, if one is given.
+    fn install(self) -> Result<(), Box<dyn std::error::Error>> {
+        let cassette = match (self.record, self.replay) {
+            (Some(dir), _) => Cassette::record(dir)?,
+            (None, Some(dir)) => Cassette::replay(dir)?,
+            (None, None) => return Ok(()),
+        };
+        if !Client::set_shared(Client::builder().cassette(cassette).build()?) {
+            return Err("the shared client is already in use".into());
+        }
+        Ok(())
+    }
+}
+
+#[derive(Args)]
+#[group(multiple = false)]
+struct TrackArgs {
+    /// Use the reduced track with fewer points
+    #[arg(long)]
+    reduced: bool,
+    /// Replay the track as it was at this time (RFC 3339), rounded down to 5 minutes
+    #[arg(long)]
+    at: Option<DateTime<Utc>>,
+}
+
+impl From<TrackArgs> for TrackSource {
+    fn from(args: TrackArgs) -> Self {
+        match (args.reduced, args.at) {
+            (_, Some(at)) => TrackSource::At(at),
+            (true, None) => TrackSource::Reduced,
+            (false, None) => TrackSource::Latest,
+        }
+    }
+}
 
 #[tokio::main]
 async fn main() {
-    match Overview::request().await {
-        Ok(overview) => {
-            println!("{:?}", overview);
+    let Cli {
+        json,
+        cassette,
+        command,
+    } = Cli::parse();
+    let result = match cassette.install() {
+        Ok(()) => run(json, command).await,
+        Err(err) => Err(err),
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        std::process::exit(1);
+    }
+}
+
+async fn run(json: bool, command: Command) -> Result<(), Box<dyn std::error::Error>> {
+    match command {
+        Command::Overview => cli::overview(json).await,
+        Command::Status => cli::status(json).await,
+        Command::Track { athlete, source } => cli::track(&athlete, source.into(), json).await,
+        Command::Replay { date } => cli::replay(date, json).await,
+        Command::Export {
+            athlete,
+            format,
+            output,
+            source,
+        } => cli::export(&athlete, source.into(), format, output.as_deref()).await,
+        Command::SchemaCheck => cli::schema_check(json).await,
+        #[cfg(feature = "tui")]
+        Command::Tui { interval } => cli::tui::run(Duration::from_secs(interval)).await,
+        #[cfg(feature = "server")]
+        Command::Serve {
+            listen,
+            status_ttl,
+            track_ttl,
+            push_interval,
+        } => serve(
+            listen,
+            ProxyConfig {
+                status_ttl: Duration::from_secs(status_ttl),
+                track_ttl: Duration::from_secs(track_ttl),
+                push_interval: Duration::from_secs(push_interval),
+                ..ProxyConfig::default()
+            },
+        )
+        .await
+        .map_err(Into::into),
+        Command::Notify { config, interval } => notify(config, Duration::from_secs(interval)).await,
+        Command::Archive {
+            database,
+            status_interval,
+            track_interval,
+            gap_threshold,
+        } => {
+            archive(
+                database,
+                ArchiveConfig {
+                    status_interval: Duration::from_secs(status_interval),
+                    track_interval: Duration::from_secs(track_interval),
+                    gap_threshold: Duration::from_secs(gap_threshold),
+                    ..ArchiveConfig::default()
+                },
+            )
+            .await
         }
-        Err(err_content) => {
-            print!("Failed to get Overview: {}", err_content);
+    }
+}
+
+#[cfg(feature = "server")]
+async fn serve(listen: SocketAddr, config: ProxyConfig) -> Result<(), xalps::Error> {
+    #[cfg(feature = "metrics")]
+    let proxy = {
+        let metrics = Arc::new(Metrics::new());
+        let client = Client::shared().with_observer(metrics.clone());
+        Proxy::new(client, config)?.with_metrics(metrics)
+    };
+    #[cfg(not(feature = "metrics"))]
+    let proxy = Proxy::new(Client::shared().clone(), config)?;
+    let proxy = proxy.on_poll_error(|err| eprintln!("{}", err));
+    println!("serving on http://{}", listen);
+    server::serve(listen, Arc::new(proxy)).await
+}
+
+async fn notify(config: PathBuf, interval: Duration) -> Result<(), Box<dyn std::error::Error>> {
+    let config = NotifierConfig::load(&config)
+        .map_err(|err| format!("failed to load {}: {}", config.display(), err))?;
+    let client = Client::shared();
+    let notifier = Notifier::new(config, Overview::request_with(client).await?)?
+        .on_error(|err| eprintln!("{}", err));
+    println!("watching the race for events");
+    tokio::select! {
+        result = notifier.run(client, interval) => result?,
+        _ = tokio::signal::ctrl_c() => println!("stopped notifying"),
+    }
+    Ok(())
+}
+
+async fn archive(
+    database: PathBuf,
+    config: ArchiveConfig,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let storage = Storage::open(&database)
+        .map_err(|err| format!("failed to open {}: {}", database.display(), err))?;
+    let mut archiver = Archiver::new(Client::shared().clone(), storage, config)?.with_observer(
+        |event: &ArchiveEvent| {
+            if event.is_error() {
+                eprintln!("{}", event);
+            } else {
+                println!("{}", event);
+            }
+        },
+    );
+    tokio::select! {
+        result = archiver.run() => {
+            result.map_err(|err| format!("failed to archive the race: {}", err))?;
         }
+        _ = tokio::signal::ctrl_c() => println!("stopped archiving"),
     }
+    Ok(())
 }
@@ -0,0 +1,245 @@
+//! Prometheus metrics of the race and of the health of the feeds.
+//!
+//! Race gauges are labelled with `athlete_id` and `athlete` (the full name):
+//!
+//! - `xalps_athlete_distance_to_goal_km`
+//! - `xalps_athlete_altitude_meters`
+//! - `xalps_athlete_speed`, of the newest track point as reported by the feed
+//! - `xalps_athlete_status`, 1 for the current `status` label and 0 for the others
+//! - `xalps_athlete_seconds_since_fix`, age of the newest track point, or of
+//!   the race status if there is no track
+//!
+//! Feed health is collected by installing [`Metrics`] as the client's
+//! [`FeedObserver`], labelled with the [`endpoint`](crate::endpoint):
+//!
+//! - `xalps_feed_request_duration_seconds`
+//! - `xalps_feed_request_errors_total`
+//! - `xalps_feed_decode_failures_total`, also labelled with `format`
+
+use crate::{Athlete, AthleteStatus, Error, FeedObserver, Overview, RaceStatus, Track};
+use chrono::{DateTime, Utc};
+use prometheus::{
+    Encoder, GaugeVec, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use std::time::Duration;
+
+const STATUSES: [AthleteStatus; 3] = [AthleteStatus::Fly, AthleteStatus::Hike, AthleteStatus::Rest];
+
+#[derive(Debug)]
+pub struct Metrics {
+    registry: Registry,
+    distance_to_goal: GaugeVec,
+    altitude: GaugeVec,
+    speed: GaugeVec,
+    status: GaugeVec,
+    seconds_since_fix: GaugeVec,
+    request_duration: HistogramVec,
+    request_errors: IntCounterVec,
+    decode_failures: IntCounterVec,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Metrics::new()
+    }
+}
+
+impl Metrics {
+    pub fn new() -> Metrics {
+        let registry = Registry::new();
+        let athlete = ["athlete_id", "athlete"];
+        let gauge = |name: &str, help: &str, labels: &[&str]| {
+            let gauge = GaugeVec::new(Opts::new(name, help), labels).unwrap();
+            registry.register(Box::new(gauge.clone())).unwrap();
+            gauge
+        };
+        let counter = |name: &str, help: &str, labels: &[&str]| {
+            let counter = IntCounterVec::new(Opts::new(name, help), labels).unwrap();
+            registry.register(Box::new(counter.clone())).unwrap();
+            counter
+        };
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "xalps_feed_request_duration_seconds",
+                "Latency of requests to the feeds",
+            ),
+            &["endpoint"],
+        )
+        .unwrap();
+        registry
+            .register(Box::new(request_duration.clone()))
+            .unwrap();
+
+        Metrics {
+            distance_to_goal: gauge(
+                "xalps_athlete_distance_to_goal_km",
+                "Distance of the athlete to goal",
+                &athlete,
+            ),
+            altitude: gauge(
+                "xalps_athlete_altitude_meters",
+                "Altitude of the athlete",
+                &athlete,
+            ),
+            speed: gauge(
+                "xalps_athlete_speed",
+                "Speed of the athlete's newest track point",
+                &athlete,
+            ),
+            status: gauge(
+                "xalps_athlete_status",
+                "1 for the athlete's current status, 0 for the others",
+                &["athlete_id", "athlete", "status"],
+            ),
+            seconds_since_fix: gauge(
+                "xalps_athlete_seconds_since_fix",
+                "Seconds since the athlete's newest position",
+                &athlete,
+            ),
+            request_duration,
+            request_errors: counter(
+                "xalps_feed_request_errors_total",
+                "Failed requests to the feeds",
+                &["endpoint"],
+            ),
+            decode_failures: counter(
+                "xalps_feed_decode_failures_total",
+                "Feed responses that could not be decoded",
+                &["endpoint", "format"],
+            ),
+            registry,
+        }
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Sets the race gauges of every athlete in `status`.
+    pub fn observe_race<'a>(
+        &self,
+        overview: &Overview,
+        status: &[RaceStatus],
+        tracks: impl IntoIterator<Item = &'a Track>,
+        now: DateTime<Utc>,
+    ) {
+        let tracks: Vec<&Track> = tracks.into_iter().collect();
+        let index = overview.athlete_index();
+        for status in status {
+            let name = index
+                .by_id(status.athlete_id)
+                .map(Athlete::full_name)
+                .unwrap_or_default();
+            let athlete_id = status.athlete_id.to_string();
+            let labels = [athlete_id.as_str(), name.as_str()];
+            self.distance_to_goal
+                .with_label_values(&labels)
+                .set(status.distance_to_goal);
+            self.altitude
+                .with_label_values(&labels)
+                .set(status.altitude as f64);
+            for known in STATUSES {
+                self.status
+                    .with_label_values(&[&athlete_id, &name, known.as_str()])
+                    .set(if known == status.status { 1.0 } else { 0.0 });
+            }
+
+            let newest = tracks
+                .iter()
+                .filter(|track| track.athlete_id == status.athlete_id)
+                .filter_map(|track| track.points.last())
+                .max_by(|a, b| a.timestamp.total_cmp(&b.timestamp));
+            let fix = match newest {
+                Some(point) => {
+                    self.speed
+                        .with_label_values(&labels)
+                        .set(point.speed as f64);
+                    point.timestamp
+                }
+                None => status.timestamp as f64,
+            };
+            self.seconds_since_fix
+                .with_label_values(&labels)
+                .set((now.timestamp_millis() as f64 / 1000.0 - fix).max(0.0));
+        }
+    }
+
+    /// All metrics in the Prometheus text format.
+    pub fn encode(&self) -> String {
+        let mut buffer = Vec::new();
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("text encoding into memory can't fail");
+        String::from_utf8(buffer).expect("the text format is utf-8")
+    }
+}
+
+impl FeedObserver for Metrics {
+    fn request(&self, endpoint: &str, latency: Duration, error: Option<&Error>) {
+        self.request_duration
+            .with_label_values(&[endpoint])
+            .observe(latency.as_secs_f64());
+        if error.is_some() {
+            self.request_errors.with_label_values(&[endpoint]).inc();
+        }
+    }
+
+    fn decode_failed(&self, endpoint: &str, format: &str, _error: &Error) {
+        self.decode_failures
+            .with_label_values(&[endpoint, format])
+            .inc();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Metrics;
+    use crate::{test_util::MockServer, ApiTrackResponse, AthleteId, Overview, RaceStatus, Track};
+    use chrono::{TimeZone, Utc};
+    use std::sync::Arc;
+
+    #[tokio::test]
+    async fn race_and_feed_health() {
+        let server = MockServer::start().await;
+        let metrics = Arc::new(Metrics::new());
+        let client = server
+            .client_builder()
+            .observer(metrics.clone())
+            .build()
+            .unwrap();
+        server.fail("/race/race-status", 503, 1);
+        server.route("/race/athlete/3/track/latest.pbf", "not protobuf");
+
+        let overview = Overview::request_with(&client).await.unwrap();
+        let status = RaceStatus::request_with(&client).await.unwrap();
+        let track = Track::from(
+            ApiTrackResponse::request_with(&client, AthleteId::new(29))
+                .await
+                .unwrap(),
+        );
+        assert!(ApiTrackResponse::request_with(&client, AthleteId::new(3))
+            .await
+            .is_err());
+        let now = Utc.timestamp_opt(track.points.last().unwrap().timestamp as i64 + 90, 0);
+        metrics.observe_race(&overview, &status, [&track], now.unwrap());
+
+        let text = metrics.encode();
+        let lines: Vec<&str> = text.lines().collect();
+        for expected in [
+            r#"xalps_athlete_distance_to_goal_km{athlete="Christian Maurer",athlete_id="29"} 689.4"#,
+            r#"xalps_athlete_status{athlete="Christian Maurer",athlete_id="29",status="FLY"} 1"#,
+            r#"xalps_athlete_status{athlete="Christian Maurer",athlete_id="29",status="HIKE"} 0"#,
+            r#"xalps_athlete_seconds_since_fix{athlete="Christian Maurer",athlete_id="29"} 90"#,
+            r#"xalps_feed_request_errors_total{endpoint="/race/race-status"} 1"#,
+            r#"xalps_feed_request_duration_seconds_count{endpoint="/race/race-status"} 2"#,
+            r#"xalps_feed_decode_failures_total{endpoint="/race/athlete/{id}/track/latest.pbf",format="protobuf"} 1"#,
+        ] {
+            assert!(
+                lines.contains(&expected),
+                "missing {} in\n{}",
+                expected,
+                text
+            );
+        }
+    }
+}
@@ -0,0 +1,506 @@
+//! Posts detected [`RaceEvent`]s to HTTP webhooks, e.g. a chat channel.
+//!
+//! Webhooks are configured in a JSON file:
+//!
+//! ```json
+//! {
+//!   "dedupWindowSecs": 900,
+//!   "delivery": { "timeoutSecs": 30, "maxRetries": 3, "initialBackoffMs": 500, "maxBackoffSecs": 30 },
+//!   "webhooks": [
+//!     {
+//!       "url": "https://hooks.slack.com/services/...",
+//!       "athletes": ["29", "3"],
+//!       "events": ["takeoff", "turnPointReached"],
+//!       "template": { "text": "{{message}} at {{time}}" }
+//!     }
+//!   ]
+//! }
+//! ```
+//!
+//! Strings in the template may contain `{{type}}`, `{{athleteId}}`,
+//! `{{athlete}}`, `{{message}}`, `{{time}}`, `{{turnpoint}}` and
+//! `{{previousLeader}}`. Without a template the event is posted as
+//! `{"event": {...}, "athlete": "...", "message": "..."}`. Empty `athletes`
+//! or `events` match everything. Every `delivery` field is optional.
+
+use crate::{
+    events::{EventDetector, RaceEvent},
+    Athlete, AthleteId, AthleteStatus, Client, Error, Overview, RaceStatus, RequestPolicy, Url,
+};
+use chrono::{TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::{
+    collections::HashMap,
+    fmt,
+    path::Path,
+    sync::Mutex,
+    time::{Duration, Instant},
+};
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub url: Url,
+    /// Only events about these athlete ids.
+    #[serde(default)]
+    pub athletes: Vec<AthleteId>,
+    /// Only these kinds of events, see [`RaceEvent::kind`].
+    #[serde(default)]
+    pub events: Vec<String>,
+    /// JSON body with placeholders.
+    #[serde(default)]
+    pub template: Option<Value>,
+}
+
+impl Webhook {
+    fn matches(&self, event: &RaceEvent) -> bool {
+        (self.athletes.is_empty() || self.athletes.iter().any(|&id| event.concerns(id)))
+            && (self.events.is_empty() || self.events.iter().any(|kind| kind == event.kind()))
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct NotifierConfig {
+    pub webhooks: Vec<Webhook>,
+    /// The same event for the same athlete is posted to a webhook only once in this many seconds.
+    #[serde(default = "default_dedup_window_secs")]
+    pub dedup_window_secs: u64,
+    #[serde(default)]
+    pub delivery: Delivery,
+}
+
+fn default_dedup_window_secs() -> u64 {
+    15 * 60
+}
+
+/// Timeout and retries of every delivery.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Delivery {
+    /// Upper bound for a single attempt.
+    pub timeout_secs: u64,
+    /// How many times a delivery is repeated after a transient failure.
+    pub max_retries: u32,
+    /// Delay before the first retry; doubled on every further attempt.
+    pub initial_backoff_ms: u64,
+    /// Cap for the exponential backoff.
+    pub max_backoff_secs: u64,
+}
+
+impl Default for Delivery {
+    fn default() -> Self {
+        let policy = RequestPolicy::default();
+        Delivery {
+            timeout_secs: policy.timeout.as_secs(),
+            max_retries: policy.max_retries,
+            initial_backoff_ms: policy.initial_backoff.as_millis() as u64,
+            max_backoff_secs: policy.max_backoff.as_secs(),
+        }
+    }
+}
+
+impl Delivery {
+    /// The delivery settings as a policy, without a rate limit.
+    pub fn policy(&self) -> RequestPolicy {
+        RequestPolicy {
+            timeout: Duration::from_secs(self.timeout_secs),
+            max_retries: self.max_retries,
+            initial_backoff: Duration::from_millis(self.initial_backoff_ms),
+            max_backoff: Duration::from_secs(self.max_backoff_secs),
+            rate_limit: None,
+        }
+    }
+}
+
+/// A failure while notifying, see [`Notifier::on_error`].
+#[derive(Debug)]
+pub enum NotifyError {
+    /// The race status could not be fetched, its events are detected on a later poll.
+    Poll(Error),
+    /// A webhook could not be sent an event, even after retrying.
+    Delivery {
+        url: Url,
+        kind: &'static str,
+        athlete_id: AthleteId,
+        error: Error,
+    },
+}
+
+impl fmt::Display for NotifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotifyError::Poll(err) => write!(f, "failed to poll race status: {}", err),
+            NotifyError::Delivery {
+                url,
+                kind,
+                athlete_id,
+                error,
+            } => write!(
+                f,
+                "failed to post {} of athlete {} to {}: {}",
+                kind, athlete_id, url, error
+            ),
+        }
+    }
+}
+
+type ErrorObserver = Box<dyn Fn(&NotifyError) + Send + Sync>;
+
+impl NotifierConfig {
+    pub fn load(path: impl AsRef<Path>) -> Result<NotifierConfig, Error> {
+        Ok(serde_json::from_slice(&std::fs::read(path)?)?)
+    }
+}
+
+pub struct Notifier {
+    http: reqwest::Client,
+    config: NotifierConfig,
+    policy: RequestPolicy,
+    on_error: Option<ErrorObserver>,
+    overview: Overview,
+    /// When an event was last posted, by webhook and event.
+    sent: Mutex<HashMap<(usize, String), Instant>>,
+}
+
+impl Notifier {
+    /// `overview` provides athlete names for messages and turnpoints for event detection.
+    pub fn new(config: NotifierConfig, overview: Overview) -> Result<Notifier, Error> {
+        let policy = config.delivery.policy();
+        Ok(Notifier {
+            http: reqwest::Client::builder()
+                .connect_timeout(policy.timeout)
+                .build()?,
+            config,
+            policy,
+            on_error: None,
+            overview,
+            sent: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Calls `observer` with every failed poll and delivery, which are
+    /// otherwise skipped silently.
+    pub fn on_error(mut self, observer: impl Fn(&NotifyError) + Send + Sync + 'static) -> Notifier {
+        self.on_error = Some(Box::new(observer));
+        self
+    }
+
+    fn failed(&self, error: NotifyError) {
+        if let Some(observer) = &self.on_error {
+            observer(&error);
+        }
+    }
+
+    /// Polls the race status every `interval` and posts the events detected between polls,
+    /// forever. Only returns if `interval` is zero.
+    pub async fn run(&self, client: &Client, interval: Duration) -> Result<(), Error> {
+        if interval.is_zero() {
+            return Err(Error::InvalidConfig(
+                "poll interval must not be zero".to_string(),
+            ));
+        }
+        let mut detector = EventDetector::new(&self.overview);
+        let mut ticks = tokio::time::interval(interval);
+        loop {
+            ticks.tick().await;
+            match RaceStatus::request_with(client).await {
+                Ok(status) => {
+                    for event in detector.update(&status) {
+                        self.notify(&event).await;
+                    }
+                }
+                Err(err) => self.failed(NotifyError::Poll(err)),
+            }
+        }
+    }
+
+    /// Posts the event to every matching webhook not already sent it within the dedup window.
+    /// Returns how many webhooks received it.
+    pub async fn notify(&self, event: &RaceEvent) -> usize {
+        let mut delivered = 0;
+        for (index, webhook) in self.config.webhooks.iter().enumerate() {
+            if !webhook.matches(event) || self.is_duplicate(index, event) {
+                continue;
+            }
+            let body = match &webhook.template {
+                Some(template) => render(template, &self.placeholders(event)),
+                None => json!({
+                    "event": event,
+                    "athlete": self.name(event.athlete_id()),
+                    "message": self.message(event),
+                }),
+            };
+            match self.post(&webhook.url, &body).await {
+                Ok(()) => {
+                    self.record_sent(index, event);
+                    delivered += 1;
+                }
+                Err(error) => self.failed(NotifyError::Delivery {
+                    url: webhook.url.clone(),
+                    kind: event.kind(),
+                    athlete_id: event.athlete_id(),
+                    error,
+                }),
+            }
+        }
+        delivered
+    }
+
+    /// Whether the event was already sent to the webhook within the dedup window.
+    fn is_duplicate(&self, webhook: usize, event: &RaceEvent) -> bool {
+        let window = Duration::from_secs(self.config.dedup_window_secs);
+        let mut sent = self.sent.lock().unwrap();
+        sent.retain(|_, at| at.elapsed() < window);
+        sent.contains_key(&(webhook, dedup_key(event)))
+    }
+
+    fn record_sent(&self, webhook: usize, event: &RaceEvent) {
+        self.sent
+            .lock()
+            .unwrap()
+            .insert((webhook, dedup_key(event)), Instant::now());
+    }
+
+    async fn post(&self, url: &Url, body: &Value) -> Result<(), Error> {
+        let mut attempt = 0;
+        loop {
+            let result = self
+                .http
+                .post(url.clone())
+                .timeout(self.policy.timeout)
+                .json(body)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(Error::from);
+            match result {
+                Ok(_) => return Ok(()),
+                Err(err) if attempt < self.policy.max_retries && err.is_transient() => {
+                    tokio::time::sleep(self.policy.backoff(attempt)).await;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+
+    fn name(&self, athlete_id: AthleteId) -> String {
+        self.overview
+            .athlete_index()
+            .by_id(athlete_id)
+            .map_or_else(|| athlete_id.to_string(), Athlete::full_name)
+    }
+
+    fn message(&self, event: &RaceEvent) -> String {
+        let name = self.name(event.athlete_id());
+        match event {
+            RaceEvent::Takeoff { .. } => format!("{} took off", name),
+            RaceEvent::Landing { status, .. } => match status {
+                AthleteStatus::Rest => format!("{} landed and is resting", name),
+                _ => format!("{} landed and is hiking", name),
+            },
+            RaceEvent::TurnPointReached { turnpoint, .. } => {
+                format!("{} reached {}", name, turnpoint)
+            }
+            RaceEvent::LeadChange {
+                previous_leader, ..
+            } => format!(
+                "{} took the lead from {}",
+                name,
+                self.name(*previous_leader)
+            ),
+        }
+    }
+
+    fn placeholders(&self, event: &RaceEvent) -> HashMap<&'static str, String> {
+        let timestamp = match event {
+            RaceEvent::Takeoff { timestamp, .. }
+            | RaceEvent::Landing { timestamp, .. }
+            | RaceEvent::TurnPointReached { timestamp, .. }
+            | RaceEvent::LeadChange { timestamp, .. } => *timestamp,
+        };
+        let mut placeholders = HashMap::from([
+            ("type", event.kind().to_string()),
+            ("athleteId", event.athlete_id().to_string()),
+            ("athlete", self.name(event.athlete_id())),
+            ("message", self.message(event)),
+            (
+                "time",
+                Utc.timestamp_opt(timestamp as i64, 0)
+                    .single()
+                    .unwrap_or_default()
+                    .to_rfc3339(),
+            ),
+            ("turnpoint", String::new()),
+            ("previousLeader", String::new()),
+        ]);
+        match event {
+            RaceEvent::TurnPointReached { turnpoint, .. } => {
+                placeholders.insert("turnpoint", turnpoint.clone());
+            }
+            RaceEvent::LeadChange {
+                previous_leader, ..
+            } => {
+                placeholders.insert("previousLeader", self.name(*previous_leader));
+            }
+            _ => {}
+        }
+        placeholders
+    }
+}
+
+/// Events with the same key are the same event for deduplication.
+fn dedup_key(event: &RaceEvent) -> String {
+    match event {
+        RaceEvent::TurnPointReached { turnpoint_id, .. } => {
+            format!("{}:{}:{}", event.kind(), event.athlete_id(), turnpoint_id)
+        }
+        _ => format!("{}:{}", event.kind(), event.athlete_id()),
+    }
+}
+
+/// Replaces `{{name}}` in every string of the template.
+fn render(template: &Value, placeholders: &HashMap<&str, String>) -> Value {
+    match template {
+        Value::String(text) => {
+            let mut text = text.clone();
+            for (name, value) in placeholders {
+                text = text.replace(&format!("{{{{{}}}}}", name), value);
+            }
+            Value::String(text)
+        }
+        Value::Array(values) => Value::Array(
+            values
+                .iter()
+                .map(|value| render(value, placeholders))
+                .collect(),
+        ),
+        Value::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, value)| (key.clone(), render(value, placeholders)))
+                .collect(),
+        ),
+        value => value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Delivery, Notifier, NotifierConfig, Webhook};
+    use crate::{events::RaceEvent, test_util, test_util::MockServer, AthleteId, Overview};
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+
+    fn notifier(webhooks: Vec<Webhook>) -> Notifier {
+        let overview: Overview =
+            serde_json::from_slice(test_util::fixture(test_util::OVERVIEW_PATH).unwrap()).unwrap();
+        let config = NotifierConfig {
+            webhooks,
+            dedup_window_secs: 60,
+            delivery: Delivery {
+                initial_backoff_ms: 10,
+                ..Delivery::default()
+            },
+        };
+        Notifier::new(config, overview).unwrap()
+    }
+
+    fn webhook(url: String, athletes: &[i32]) -> Webhook {
+        Webhook {
+            url: url.parse().unwrap(),
+            athletes: athletes.iter().copied().map(AthleteId::new).collect(),
+            events: Vec::new(),
+            template: None,
+        }
+    }
+
+    #[test]
+    fn template() {
+        let notifier = notifier(Vec::new());
+        let event = RaceEvent::LeadChange {
+            athlete_id: AthleteId::new(3),
+            timestamp: 1686761660,
+            previous_leader: AthleteId::new(29),
+        };
+        let body = super::render(
+            &json!({ "text": "{{message}} ({{type}}) at {{time}}", "who": ["{{athleteId}}"], "n": 1 }),
+            &notifier.placeholders(&event),
+        );
+        assert_eq!(
+            body,
+            json!({
+                "text": "Patrick von Känel took the lead from Christian Maurer (leadChange) at 2023-06-14T16:54:20+00:00",
+                "who": ["3"],
+                "n": 1,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn filters_retries_and_dedups() {
+        let server = MockServer::empty().await;
+        server.route("/maurer", "");
+        server.route("/pinot", "");
+        server.fail("/maurer", 503, 1);
+        let notifier = notifier(vec![
+            webhook(format!("{}/maurer", server.url()), &[29]),
+            webhook(format!("{}/pinot", server.url()), &[12]),
+        ]);
+        let takeoff = RaceEvent::Takeoff {
+            athlete_id: AthleteId::new(29),
+            timestamp: 1686761688,
+        };
+
+        assert_eq!(notifier.notify(&takeoff).await, 1);
+        assert_eq!(server.hits("/maurer"), 2);
+        assert_eq!(server.hits("/pinot"), 0);
+
+        assert_eq!(notifier.notify(&takeoff).await, 0);
+        assert_eq!(server.hits("/maurer"), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_delivery_is_not_deduped() {
+        let server = MockServer::empty().await;
+        server.route("/maurer", "");
+        server.fail("/maurer", 400, 1);
+        let errors = Arc::new(Mutex::new(Vec::new()));
+        let notifier =
+            notifier(vec![webhook(format!("{}/maurer", server.url()), &[29])]).on_error({
+                let errors = errors.clone();
+                move |error| errors.lock().unwrap().push(error.to_string())
+            });
+        let takeoff = RaceEvent::Takeoff {
+            athlete_id: AthleteId::new(29),
+            timestamp: 1686761688,
+        };
+
+        assert_eq!(notifier.notify(&takeoff).await, 0);
+        assert_eq!(notifier.notify(&takeoff).await, 1);
+        assert_eq!(server.hits("/maurer"), 2);
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].starts_with("failed to post takeoff of athlete 29 to http://"));
+    }
+
+    #[test]
+    fn delivery_config() {
+        let config: NotifierConfig = serde_json::from_value(json!({
+            "delivery": { "maxRetries": 5, "initialBackoffMs": 100 },
+            "webhooks": [{ "url": "https://hooks.example.com/xalps" }],
+        }))
+        .unwrap();
+        let policy = config.delivery.policy();
+        assert_eq!(policy.max_retries, 5);
+        assert_eq!(policy.initial_backoff.as_millis(), 100);
+        assert_eq!(policy.timeout.as_secs(), 30);
+        assert_eq!(config.dedup_window_secs, 15 * 60);
+        assert!(serde_json::from_value::<NotifierConfig>(
+            json!({ "webhooks": [{ "url": "hooks" }] })
+        )
+        .is_err());
+    }
+}
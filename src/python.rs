@@ -0,0 +1,240 @@
+//! Python bindings, built as the `xalps` extension module with
+//! [maturin](https://www.maturin.rs/): `maturin develop`, see `pyproject.toml`.
+//!
+//! ```python
+//! import pandas, xalps
+//!
+//! overview = xalps.Overview.request()
+//! track = xalps.ApiTrackResponse.request("29")
+//! points = pandas.DataFrame(track["points"])
+//! ```
+//!
+//! The overview and race status are returned as dicts and lists in the same
+//! shape as the JSON feeds. Tracks are `{"athlete_id": int, "points": {...}}`
+//! with one NumPy array per track point field (`timestamp`, `lat`, `lng`,
+//! `altitude`, `altitude_agl`, `speed`, `vertical_speed`) and a list of
+//! `status` strings or `None`, so they load into pandas or polars as columns.
+//! Requests release the GIL while waiting for the feeds.
+
+use crate::{
+    blocking::Client, ApiTrackResponse, AthleteId, Error, Overview, ParseAthleteIdError,
+    RaceStatus, RaceStatusReplay, Track, TrackPoint,
+};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use numpy::IntoPyArray;
+use pyo3::{
+    create_exception,
+    exceptions::{PyException, PyValueError},
+    prelude::*,
+    types::PyDict,
+};
+use serde::Serialize;
+
+create_exception!(
+    xalps,
+    XAlpsError,
+    PyException,
+    "Fetching or decoding a feed failed."
+);
+
+impl From<Error> for PyErr {
+    fn from(err: Error) -> PyErr {
+        XAlpsError::new_err(err.to_string())
+    }
+}
+
+impl From<ParseAthleteIdError> for PyErr {
+    fn from(err: ParseAthleteIdError) -> PyErr {
+        PyValueError::new_err(err.to_string())
+    }
+}
+
+/// Converts through JSON, so Python sees exactly what the feed contains.
+fn to_python<T: Serialize>(py: Python<'_>, value: &T) -> PyResult<Py<PyAny>> {
+    let json = serde_json::to_string(value).map_err(Error::from)?;
+    Ok(py.import("json")?.call_method1("loads", (json,))?.unbind())
+}
+
+/// One column per field of the track points, in the order of the points.
+#[derive(Debug)]
+struct Columns {
+    timestamp: Vec<f64>,
+    /// `lat`, `lng`, `altitude`, `altitude_agl`, `speed` and `vertical_speed`.
+    values: Vec<(&'static str, Vec<f32>)>,
+    status: Vec<Option<String>>,
+}
+
+impl Columns {
+    fn new(track: &Track) -> Columns {
+        let column = |field: fn(&TrackPoint) -> f32| track.points.iter().map(field).collect();
+        Columns {
+            timestamp: track.points.iter().map(|point| point.timestamp).collect(),
+            values: vec![
+                ("lat", column(|point| point.lat)),
+                ("lng", column(|point| point.lng)),
+                ("altitude", column(|point| point.altitude)),
+                ("altitude_agl", column(|point| point.altitude_agl)),
+                ("speed", column(|point| point.speed)),
+                ("vertical_speed", column(|point| point.vertical_speed)),
+            ],
+            status: track
+                .points
+                .iter()
+                .map(|point| point.status.clone())
+                .collect(),
+        }
+    }
+}
+
+/// Columns of the track points as NumPy arrays.
+fn track_to_python(py: Python<'_>, track: &Track) -> PyResult<Py<PyAny>> {
+    let columns = Columns::new(track);
+    let points = PyDict::new(py);
+    points.set_item("timestamp", columns.timestamp.into_pyarray(py))?;
+    for (name, values) in columns.values {
+        points.set_item(name, values.into_pyarray(py))?;
+    }
+    points.set_item("status", columns.status)?;
+
+    let result = PyDict::new(py);
+    result.set_item("athlete_id", track.athlete_id.get())?;
+    result.set_item("points", points)?;
+    Ok(result.into_any().unbind())
+}
+
+#[pyclass(module = "xalps", name = "Overview", frozen)]
+struct PyOverview;
+
+#[pymethods]
+impl PyOverview {
+    #[staticmethod]
+    fn request(py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let overview = py.detach(|| Overview::request_blocking_with(Client::shared()))?;
+        to_python(py, &overview)
+    }
+}
+
+#[pyclass(module = "xalps", name = "RaceStatus", frozen)]
+struct PyRaceStatus;
+
+#[pymethods]
+impl PyRaceStatus {
+    #[staticmethod]
+    fn request(py: Python<'_>) -> PyResult<Py<PyAny>> {
+        let status = py.detach(|| RaceStatus::request_blocking_with(Client::shared()))?;
+        to_python(py, &status)
+    }
+}
+
+#[pyclass(module = "xalps", name = "RaceStatusReplay", frozen)]
+struct PyRaceStatusReplay;
+
+#[pymethods]
+impl PyRaceStatusReplay {
+    /// Every race status snapshot of the day, today if not given.
+    #[staticmethod]
+    #[pyo3(signature = (date=None))]
+    fn request(py: Python<'_>, date: Option<NaiveDate>) -> PyResult<Py<PyAny>> {
+        let day = match date {
+            Some(date) => Local
+                .from_local_datetime(&date.and_time(NaiveTime::MIN))
+                .earliest()
+                .ok_or_else(|| XAlpsError::new_err("invalid date"))?,
+            None => Local::now(),
+        };
+        let replay =
+            py.detach(|| RaceStatusReplay::request_blocking_with(Client::shared(), day))?;
+        to_python(py, &replay)
+    }
+}
+
+#[pyclass(module = "xalps", name = "ApiTrackResponse", frozen)]
+struct PyApiTrackResponse;
+
+#[pymethods]
+impl PyApiTrackResponse {
+    #[staticmethod]
+    fn request(py: Python<'_>, athlete_id: &str) -> PyResult<Py<PyAny>> {
+        let athlete_id: AthleteId = athlete_id.parse()?;
+        let response =
+            py.detach(|| ApiTrackResponse::request_blocking_with(Client::shared(), athlete_id))?;
+        track_to_python(py, &Track::from(response))
+    }
+
+    /// The track as it was at `time`, a timezone aware datetime on a 5 minute boundary.
+    #[staticmethod]
+    fn request_replay(
+        py: Python<'_>,
+        athlete_id: &str,
+        time: DateTime<Utc>,
+    ) -> PyResult<Py<PyAny>> {
+        let athlete_id: AthleteId = athlete_id.parse()?;
+        let response = py.detach(|| {
+            ApiTrackResponse::request_replay_blocking_with(Client::shared(), athlete_id, time)
+        })?;
+        track_to_python(py, &Track::from(response))
+    }
+
+    #[staticmethod]
+    fn request_reduced(py: Python<'_>, athlete_id: &str) -> PyResult<Py<PyAny>> {
+        let athlete_id: AthleteId = athlete_id.parse()?;
+        let response = py.detach(|| {
+            ApiTrackResponse::request_reduced_blocking_with(Client::shared(), athlete_id)
+        })?;
+        track_to_python(py, &Track::from(response))
+    }
+
+    /// Decodes the bytes of a `.pbf` track feed.
+    #[staticmethod]
+    fn decode(py: Python<'_>, data: &[u8]) -> PyResult<Py<PyAny>> {
+        track_to_python(py, &Track::from(ApiTrackResponse::decode(data)?))
+    }
+}
+
+#[pymodule]
+fn xalps(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyOverview>()?;
+    m.add_class::<PyRaceStatus>()?;
+    m.add_class::<PyRaceStatusReplay>()?;
+    m.add_class::<PyApiTrackResponse>()?;
+    m.add("XAlpsError", m.py().get_type::<XAlpsError>())?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::Columns;
+    use crate::{test_util, ApiTrackResponse, Track};
+
+    #[test]
+    fn columns() {
+        let track = Track::from(
+            ApiTrackResponse::decode(
+                test_util::fixture("/race/athlete/29/track/latest.pbf").unwrap(),
+            )
+            .unwrap(),
+        );
+        let columns = Columns::new(&track);
+        let names: Vec<&str> = columns.values.iter().map(|(name, _)| *name).collect();
+        assert_eq!(
+            names,
+            [
+                "lat",
+                "lng",
+                "altitude",
+                "altitude_agl",
+                "speed",
+                "vertical_speed"
+            ]
+        );
+        assert_eq!(columns.timestamp.len(), track.points.len());
+        assert_eq!(columns.status.len(), track.points.len());
+        for (index, point) in track.points.iter().enumerate() {
+            assert_eq!(columns.timestamp[index], point.timestamp);
+            assert_eq!(columns.values[0].1[index], point.lat);
+            assert_eq!(columns.values[1].1[index], point.lng);
+            assert_eq!(columns.values[5].1[index], point.vertical_speed);
+            assert_eq!(columns.status[index], point.status);
+        }
+    }
+}
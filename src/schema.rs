@@ -0,0 +1,488 @@
+//! Detects when the JSON feeds drift away from the models.
+//!
+//! The models are strict serde structs: a field upstream adds is silently
+//! dropped, and a removed or retyped one fails decoding in the middle of the
+//! race. [`check`] fetches every JSON feed and compares its shape with the
+//! JSON Schema of its model, `xalps schema-check` prints the result.
+//! [`assert_matches`] does the same for a recorded body in tests.
+
+use crate::{Client, Error, Overview, RaceStatus, RaceStatusReplay};
+use chrono::{Local, TimeZone};
+use schemars::{schema_for, JsonSchema};
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::fmt;
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum JsonType {
+    Null,
+    Boolean,
+    Integer,
+    Number,
+    String,
+    Array,
+    Object,
+}
+
+impl JsonType {
+    pub fn of(value: &Value) -> JsonType {
+        match value {
+            Value::Null => JsonType::Null,
+            Value::Bool(_) => JsonType::Boolean,
+            Value::Number(number) if number.is_f64() => JsonType::Number,
+            Value::Number(_) => JsonType::Integer,
+            Value::String(_) => JsonType::String,
+            Value::Array(_) => JsonType::Array,
+            Value::Object(_) => JsonType::Object,
+        }
+    }
+
+    fn parse(name: &str) -> Option<JsonType> {
+        Some(match name {
+            "null" => JsonType::Null,
+            "boolean" => JsonType::Boolean,
+            "integer" => JsonType::Integer,
+            "number" => JsonType::Number,
+            "string" => JsonType::String,
+            "array" => JsonType::Array,
+            "object" => JsonType::Object,
+            _ => return None,
+        })
+    }
+
+    /// Whether a model field of this type decodes a value of type `found`.
+    fn accepts(self, found: JsonType) -> bool {
+        self == found || (self == JsonType::Number && found == JsonType::Integer)
+    }
+}
+
+impl fmt::Display for JsonType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            JsonType::Null => "null",
+            JsonType::Boolean => "boolean",
+            JsonType::Integer => "integer",
+            JsonType::Number => "number",
+            JsonType::String => "string",
+            JsonType::Array => "array",
+            JsonType::Object => "object",
+        };
+        f.write_str(name)
+    }
+}
+
+/// A difference between a feed and its model, at a path like
+/// `$.athletes[].lastname` where `[]` stands for every element.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "change", rename_all = "camelCase")]
+pub enum Change {
+    /// A field the model doesn't have, dropped when decoding.
+    Added { path: String, found: JsonType },
+    /// A field the model requires is missing, decoding fails.
+    Removed { path: String },
+    /// A value of a type the model doesn't accept, decoding fails.
+    TypeChanged {
+        path: String,
+        expected: Vec<JsonType>,
+        found: JsonType,
+    },
+    /// A string the model's enum doesn't know, e.g. a new athlete status;
+    /// decoding fails.
+    UnknownValue { path: String, value: Value },
+}
+
+impl Change {
+    pub fn path(&self) -> &str {
+        match self {
+            Change::Added { path, .. }
+            | Change::Removed { path }
+            | Change::TypeChanged { path, .. }
+            | Change::UnknownValue { path, .. } => path,
+        }
+    }
+
+    /// Whether the feed no longer decodes into the model.
+    pub fn breaks_decoding(&self) -> bool {
+        !matches!(self, Change::Added { .. })
+    }
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Change::Added { path, found } => write!(f, "added {} ({})", path, found),
+            Change::Removed { path } => write!(f, "removed {}", path),
+            Change::TypeChanged {
+                path,
+                expected,
+                found,
+            } => {
+                let expected: Vec<String> = expected.iter().map(JsonType::to_string).collect();
+                write!(
+                    f,
+                    "changed {} from {} to {}",
+                    path,
+                    expected.join(" or "),
+                    found
+                )
+            }
+            Change::UnknownValue { path, value } => {
+                write!(f, "unknown value {} at {}", value, path)
+            }
+        }
+    }
+}
+
+/// Changes of `value` against the JSON shape of `T`, each reported once
+/// however many array elements have it.
+pub fn diff<T: JsonSchema>(value: &Value) -> Vec<Change> {
+    let root = serde_json::to_value(schema_for!(T)).expect("schemas serialise to JSON");
+    let mut differ = Differ {
+        definitions: root.get("definitions"),
+        changes: Vec::new(),
+    };
+    differ.walk("$".to_string(), &root, value);
+    differ.changes
+}
+
+/// Panics listing the changes if `json` doesn't match the shape of `T`,
+/// e.g. to check that recorded fixtures still match the models.
+pub fn assert_matches<T: JsonSchema>(json: &[u8]) {
+    let value: Value = serde_json::from_slice(json).expect("invalid json");
+    let changes = diff::<T>(&value);
+    assert!(
+        changes.is_empty(),
+        "the json drifted from {}:\n{}",
+        T::schema_name(),
+        changes
+            .iter()
+            .map(Change::to_string)
+            .collect::<Vec<_>>()
+            .join("\n")
+    );
+}
+
+struct Differ<'a> {
+    definitions: Option<&'a Value>,
+    changes: Vec<Change>,
+}
+
+impl<'a> Differ<'a> {
+    /// Follows `$ref`s, also when wrapped in an `allOf` to carry a description.
+    fn resolve(&self, mut schema: &'a Value) -> &'a Value {
+        loop {
+            if let Some(name) = schema
+                .get("$ref")
+                .and_then(Value::as_str)
+                .and_then(|reference| reference.strip_prefix("#/definitions/"))
+            {
+                match self
+                    .definitions
+                    .and_then(|definitions| definitions.get(name))
+                {
+                    Some(definition) => schema = definition,
+                    None => return schema,
+                }
+            } else if let Some([single]) = schema
+                .get("allOf")
+                .and_then(Value::as_array)
+                .map(Vec::as_slice)
+            {
+                schema = single;
+            } else {
+                return schema;
+            }
+        }
+    }
+
+    /// Types the schema allows, empty for any.
+    fn types(&self, schema: &'a Value) -> Vec<JsonType> {
+        let schema = self.resolve(schema);
+        if let Some(alternatives) = schema.get("anyOf").and_then(Value::as_array) {
+            let mut types = Vec::new();
+            for alternative in alternatives {
+                let alternative = self.types(alternative);
+                if alternative.is_empty() {
+                    return alternative;
+                }
+                for t in alternative {
+                    if !types.contains(&t) {
+                        types.push(t);
+                    }
+                }
+            }
+            return types;
+        }
+        match schema.get("type") {
+            Some(Value::String(name)) => JsonType::parse(name).into_iter().collect(),
+            Some(Value::Array(names)) => names
+                .iter()
+                .filter_map(|name| name.as_str().and_then(JsonType::parse))
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn push(&mut self, change: Change) {
+        if !self.changes.contains(&change) {
+            self.changes.push(change);
+        }
+    }
+
+    fn walk(&mut self, path: String, schema: &'a Value, value: &Value) {
+        let schema = self.resolve(schema);
+        let found = JsonType::of(value);
+        if let Some(alternatives) = schema.get("anyOf").and_then(Value::as_array) {
+            // e.g. `Option<Struct>`: the struct or null.
+            let alternative = alternatives.iter().find(|alternative| {
+                let types = self.types(alternative);
+                types.is_empty() || types.iter().any(|t| t.accepts(found))
+            });
+            match alternative {
+                Some(alternative) => self.walk(path, alternative, value),
+                None => self.push(Change::TypeChanged {
+                    expected: self.types(schema),
+                    path,
+                    found,
+                }),
+            }
+            return;
+        }
+
+        let expected = self.types(schema);
+        if !expected.is_empty() && !expected.iter().any(|t| t.accepts(found)) {
+            self.push(Change::TypeChanged {
+                path,
+                expected,
+                found,
+            });
+            return;
+        }
+        if let Some(allowed) = schema.get("enum").and_then(Value::as_array) {
+            if !allowed.contains(value) {
+                self.push(Change::UnknownValue {
+                    path,
+                    value: value.clone(),
+                });
+            }
+            return;
+        }
+        match value {
+            Value::Object(fields) => self.walk_object(&path, schema, fields),
+            Value::Array(items) => {
+                if let Some(item_schema) = schema.get("items") {
+                    for item in items {
+                        self.walk(format!("{}[]", path), item_schema, item);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn walk_object(&mut self, path: &str, schema: &'a Value, fields: &Map<String, Value>) {
+        // Without properties the model takes any object, e.g. a map.
+        let Some(properties) = schema.get("properties").and_then(Value::as_object) else {
+            return;
+        };
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|names| names.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        for (name, property) in properties {
+            let field_path = format!("{}.{}", path, name);
+            match fields.get(name) {
+                Some(field) => self.walk(field_path, property, field),
+                None if required.contains(&name.as_str()) => {
+                    self.push(Change::Removed { path: field_path })
+                }
+                None => {}
+            }
+        }
+        for (name, field) in fields {
+            if !properties.contains_key(name) {
+                self.push(Change::Added {
+                    path: format!("{}.{}", path, name),
+                    found: JsonType::of(field),
+                });
+            }
+        }
+    }
+}
+
+/// Changes of one feed against its model.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct FeedDrift {
+    pub feed: &'static str,
+    pub url: String,
+    pub changes: Vec<Change>,
+    /// Why the feed couldn't be fetched or parsed as JSON, `changes` is empty then.
+    pub error: Option<String>,
+}
+
+impl FeedDrift {
+    fn compare<T: JsonSchema>(
+        feed: &'static str,
+        url: String,
+        fetched: &Result<Value, Error>,
+    ) -> FeedDrift {
+        match fetched {
+            Ok(value) => FeedDrift {
+                feed,
+                url,
+                changes: diff::<T>(value),
+                error: None,
+            },
+            Err(err) => FeedDrift {
+                feed,
+                url,
+                changes: Vec::new(),
+                error: Some(err.to_string()),
+            },
+        }
+    }
+
+    /// Whether the feed was fetched and matches its model.
+    pub fn is_clean(&self) -> bool {
+        self.error.is_none() && self.changes.is_empty()
+    }
+}
+
+/// Fetches the overview, the race status and the race status replay of the
+/// day of the latest status, and compares each with its model.
+///
+/// A feed that fails to fetch is reported with its error and doesn't stop
+/// the others from being checked.
+pub async fn check(client: &Client) -> Vec<FeedDrift> {
+    let url = Overview::url(client);
+    let overview = client.get_json(&url).await;
+    let mut drift = vec![FeedDrift::compare::<Overview>("overview", url, &overview)];
+
+    let url = RaceStatus::url(client);
+    let status = client.get_json(&url).await;
+    drift.push(FeedDrift::compare::<Vec<RaceStatus>>(
+        "race status",
+        url,
+        &status,
+    ));
+
+    // Outside the race there may be no replay of today.
+    let latest = status
+        .iter()
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter_map(|status| status.get("timestamp").and_then(Value::as_i64))
+        .max();
+    let day = latest
+        .and_then(|timestamp| Local.timestamp_opt(timestamp, 0).single())
+        .unwrap_or_else(Local::now);
+    let url = RaceStatusReplay::url(client, day);
+    let replay = client.get_json(&url).await;
+    drift.push(FeedDrift::compare::<Vec<RaceStatusReplay>>(
+        "race status replay",
+        url,
+        &replay,
+    ));
+    drift
+}
+
+#[cfg(test)]
+mod test {
+    use super::{assert_matches, check, diff, Change, FeedDrift, JsonType};
+    use crate::{
+        test_util::{self, MockServer},
+        Overview, RaceStatus, RaceStatusReplay,
+    };
+    use serde_json::{json, Value};
+
+    #[test]
+    fn fixtures_match() {
+        assert_matches::<Overview>(test_util::fixture(test_util::OVERVIEW_PATH).unwrap());
+        assert_matches::<Vec<RaceStatus>>(test_util::fixture("/race/race-status").unwrap());
+        assert_matches::<Vec<RaceStatusReplay>>(
+            test_util::fixture("/race/race-status-replay_2023-06-14").unwrap(),
+        );
+    }
+
+    #[test]
+    fn changes() {
+        let mut overview: Value =
+            serde_json::from_slice(test_util::fixture(test_util::OVERVIEW_PATH).unwrap()).unwrap();
+        for athlete in overview["athletes"].as_array_mut().unwrap() {
+            athlete["teamName"] = json!("Team");
+            athlete.as_object_mut().unwrap().remove("lastname");
+        }
+        overview["athletes"][1]["hide"] = json!("no");
+        overview["turnpoints"][0]["sponsors"] = json!([{ "sponsorImg": 1 }]);
+        assert_eq!(
+            diff::<Overview>(&overview),
+            [
+                Change::Removed {
+                    path: "$.athletes[].lastname".to_string(),
+                },
+                Change::Added {
+                    path: "$.athletes[].teamName".to_string(),
+                    found: JsonType::String,
+                },
+                Change::TypeChanged {
+                    path: "$.athletes[].hide".to_string(),
+                    expected: vec![JsonType::Boolean],
+                    found: JsonType::String,
+                },
+                Change::TypeChanged {
+                    path: "$.turnpoints[].sponsors[].sponsorImg".to_string(),
+                    expected: vec![JsonType::String, JsonType::Null],
+                    found: JsonType::Integer,
+                },
+            ]
+        );
+
+        let status = json!([
+            { "athleteId": "29", "timestamp": 1, "status": "RETIRED", "distanceToGoal": 1, "altitude": 2.5 },
+        ]);
+        assert_eq!(
+            diff::<Vec<RaceStatus>>(&status),
+            [
+                Change::TypeChanged {
+                    path: "$[].altitude".to_string(),
+                    expected: vec![JsonType::Integer],
+                    found: JsonType::Number,
+                },
+                Change::UnknownValue {
+                    path: "$[].status".to_string(),
+                    value: json!("RETIRED"),
+                },
+            ]
+        );
+        assert!(diff::<Vec<RaceStatus>>(&json!({}))
+            .iter()
+            .all(Change::breaks_decoding));
+    }
+
+    #[tokio::test]
+    async fn check_feeds() {
+        let server = MockServer::start().await;
+        let drift = check(&server.client()).await;
+        assert_eq!(drift.len(), 3);
+        assert!(drift.iter().all(FeedDrift::is_clean), "{:?}", drift);
+    }
+
+    #[tokio::test]
+    async fn failed_feed_is_reported() {
+        let server = MockServer::start().await;
+        server.fail(test_util::OVERVIEW_PATH, 404, 1);
+        server.fail("/race/race-status-replay_2023-06-14", 404, 1);
+        let drift = check(&server.client()).await;
+
+        let failed: Vec<&str> = drift
+            .iter()
+            .filter(|feed| feed.error.is_some())
+            .map(|feed| feed.feed)
+            .collect();
+        assert_eq!(failed, ["overview", "race status replay"]);
+        assert!(drift[1].is_clean());
+    }
+}
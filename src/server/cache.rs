@@ -0,0 +1,72 @@
+use std::{
+    collections::HashMap,
+    future::Future,
+    hash::Hash,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Values fetched from upstream, kept for a fixed time.
+///
+/// Concurrent misses for the same key may fetch twice; the feeds are cheap
+/// enough that this is not worth serialising requests for.
+pub(crate) struct TtlCache<K, V> {
+    ttl: Duration,
+    entries: Mutex<HashMap<K, (Instant, Arc<V>)>>,
+}
+
+impl<K: Eq + Hash + Clone, V> TtlCache<K, V> {
+    pub(crate) fn new(ttl: Duration) -> TtlCache<K, V> {
+        TtlCache {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// The last value fetched for `key`, even if it expired, without fetching.
+    #[cfg(any(feature = "metrics", test))]
+    pub(crate) fn cached(&self, key: &K) -> Option<Arc<V>> {
+        self.entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// The cached value for `key`, or the result of `fetch` if there is none or it expired.
+    pub(crate) async fn get_or_fetch<F, E>(&self, key: K, fetch: F) -> Result<Arc<V>, E>
+    where
+        F: Future<Output = Result<V, E>>,
+    {
+        if let Some((fetched, value)) = self.entries.lock().unwrap().get(&key) {
+            if fetched.elapsed() < self.ttl {
+                return Ok(value.clone());
+            }
+        }
+        let value = Arc::new(fetch.await?);
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(key, (Instant::now(), value.clone()));
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::TtlCache;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn expires_after_ttl() {
+        let cache = TtlCache::new(Duration::from_millis(50));
+        let fetch = |value| async move { Ok::<_, ()>(value) };
+        assert_eq!(*cache.get_or_fetch("a", fetch(1)).await.unwrap(), 1);
+        assert_eq!(*cache.get_or_fetch("a", fetch(2)).await.unwrap(), 1);
+        assert_eq!(*cache.get_or_fetch("b", fetch(3)).await.unwrap(), 3);
+        tokio::time::sleep(Duration::from_millis(60)).await;
+        assert_eq!(*cache.cached(&"a").unwrap(), 1);
+        assert_eq!(*cache.get_or_fetch("a", fetch(4)).await.unwrap(), 4);
+        assert!(cache.cached(&"c").is_none());
+    }
+}
@@ -0,0 +1,371 @@
+//! GraphQL schema over the proxy's caches, so a frontend can fetch nested data
+//! in one round trip:
+//!
+//! ```graphql
+//! {
+//!   athletes {
+//!     firstname
+//!     lastname
+//!     status { status distanceToGoal }
+//!     trackPoints(last: 10) { timestamp lat lng altitude }
+//!     nextTurnpoint { header distToGoal }
+//!   }
+//! }
+//! ```
+//!
+//! Queries are `POST`ed to `/graphql`, which also serves GraphiQL on `GET`.
+//! `subscription { raceStatus(athletes: ["29"]) { ... } }` pushes status
+//! changes over a WebSocket on `/graphql/ws`, speaking `graphql-transport-ws`
+//! or the older `graphql-ws`.
+
+use super::{
+    push::{self, Filter},
+    Proxy, Update,
+};
+use crate::{
+    Athlete, AthleteId, Overview, RaceStatus, RaceStatusReplay, Track, TrackPoint, TurnPoint,
+};
+use async_graphql::{
+    http::{GraphiQLSource, WebSocket, WebSocketProtocols, WsMessage, ALL_WEBSOCKET_PROTOCOLS},
+    ComplexObject, Context, Data, EmptyMutation, Object, Result, Schema, Subscription,
+};
+use axum::{
+    extract::{
+        ws::{CloseFrame, Message, WebSocketUpgrade},
+        State,
+    },
+    http::{header::SEC_WEBSOCKET_PROTOCOL, HeaderMap, StatusCode},
+    response::{Html, IntoResponse, Response},
+    Json,
+};
+use chrono::{Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use futures_util::{future, SinkExt, Stream, StreamExt};
+use std::{collections::HashSet, sync::Arc};
+
+pub type ProxySchema = Schema<Query, EmptyMutation, Subscription>;
+
+/// The schema, without data. Every request gets the [`Proxy`] as data.
+pub fn schema() -> ProxySchema {
+    Schema::build(Query, EmptyMutation, Subscription).finish()
+}
+
+fn proxy<'a>(ctx: &Context<'a>) -> Result<&'a Arc<Proxy>> {
+    ctx.data::<Arc<Proxy>>()
+}
+
+/// The first turnpoint the athlete is still further from goal than.
+fn next_turnpoint(turnpoints: &[TurnPoint], distance_to_goal: f64) -> Option<&TurnPoint> {
+    turnpoints
+        .iter()
+        .find(|turnpoint| turnpoint.dist_to_goal < distance_to_goal)
+}
+
+pub struct Query;
+
+#[Object]
+impl Query {
+    async fn overview(&self, ctx: &Context<'_>) -> Result<Overview> {
+        Ok(Overview::clone(&*proxy(ctx)?.overview().await?))
+    }
+
+    async fn athletes(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default)] include_hidden: bool,
+    ) -> Result<Vec<Athlete>> {
+        let overview = proxy(ctx)?.overview().await?;
+        Ok(overview
+            .athletes
+            .iter()
+            .filter(|athlete| include_hidden || !athlete.hide)
+            .cloned()
+            .collect())
+    }
+
+    /// The athlete by id or lt_slug.
+    async fn athlete(&self, ctx: &Context<'_>, id: String) -> Result<Option<Athlete>> {
+        let overview = proxy(ctx)?.overview().await?;
+        let index = overview.athlete_index();
+        Ok(id
+            .parse()
+            .ok()
+            .and_then(|id| index.by_id(id))
+            .or_else(|| index.by_slug(&id))
+            .cloned())
+    }
+
+    async fn turnpoints(&self, ctx: &Context<'_>) -> Result<Vec<TurnPoint>> {
+        Ok(proxy(ctx)?.overview().await?.turnpoints.clone())
+    }
+
+    async fn race_status(&self, ctx: &Context<'_>) -> Result<Vec<RaceStatus>> {
+        Ok(Vec::clone(&*proxy(ctx)?.race_status().await?))
+    }
+
+    /// Every race status snapshot of the day, as `YYYY-MM-DD`.
+    async fn race_status_replay(
+        &self,
+        ctx: &Context<'_>,
+        date: String,
+    ) -> Result<Vec<RaceStatusReplay>> {
+        let date: NaiveDate = date.parse()?;
+        let day = Local
+            .from_local_datetime(&date.and_time(NaiveTime::MIN))
+            .earliest()
+            .ok_or("invalid date")?;
+        Ok(Vec::clone(&*proxy(ctx)?.replay(day).await?))
+    }
+
+    /// The race status snapshot in effect at `at`, in Unix seconds.
+    async fn replay_at(&self, ctx: &Context<'_>, at: i64) -> Result<Option<RaceStatusReplay>> {
+        let at = Utc.timestamp_opt(at, 0).single().ok_or("invalid time")?;
+        Ok(proxy(ctx)?.replay_at(at).await?)
+    }
+
+    /// The athlete's latest track, if there is one.
+    async fn track(&self, ctx: &Context<'_>, athlete_id: AthleteId) -> Result<Option<Track>> {
+        match proxy(ctx)?.track(athlete_id).await {
+            Ok(track) => Ok(Some(Track::clone(&track))),
+            Err(err) if err.status() == Some(404) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
+
+#[ComplexObject]
+impl Athlete {
+    /// ISO 3166-1 alpha-2 code of the athlete's country.
+    async fn country_code(&self) -> &str {
+        self.country_code.alpha2()
+    }
+
+    async fn status(&self, ctx: &Context<'_>) -> Result<Option<RaceStatus>> {
+        let status = proxy(ctx)?.race_status().await?;
+        Ok(status
+            .iter()
+            .find(|status| status.athlete_id == self.athlete_id)
+            .cloned())
+    }
+
+    /// Points of the latest track, only the `last` ones if given.
+    async fn track_points(
+        &self,
+        ctx: &Context<'_>,
+        last: Option<usize>,
+    ) -> Result<Vec<TrackPoint>> {
+        let track = match proxy(ctx)?.track(self.athlete_id).await {
+            Ok(track) => track,
+            Err(err) if err.status() == Some(404) => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        let skip = last.map_or(0, |last| track.points.len().saturating_sub(last));
+        Ok(track.points[skip..].to_vec())
+    }
+
+    /// The next turnpoint on the athlete's way to goal.
+    async fn next_turnpoint(&self, ctx: &Context<'_>) -> Result<Option<TurnPoint>> {
+        let proxy = proxy(ctx)?;
+        let status = proxy.race_status().await?;
+        let Some(status) = status
+            .iter()
+            .find(|status| status.athlete_id == self.athlete_id)
+        else {
+            return Ok(None);
+        };
+        let overview = proxy.overview().await?;
+        Ok(next_turnpoint(&overview.turnpoints, status.distance_to_goal).cloned())
+    }
+}
+
+#[ComplexObject]
+impl TurnPoint {
+    /// ISO 3166-1 alpha-2 code of the turnpoint's country.
+    async fn country_code(&self) -> &str {
+        self.country_code.alpha2()
+    }
+}
+
+#[ComplexObject]
+impl RaceStatus {
+    async fn athlete(&self, ctx: &Context<'_>) -> Result<Option<Athlete>> {
+        let overview = proxy(ctx)?.overview().await?;
+        Ok(overview.athlete_index().by_id(self.athlete_id).cloned())
+    }
+}
+
+pub struct Subscription;
+
+#[Subscription]
+impl Subscription {
+    /// Race status of the athletes, or everyone, whenever it changes.
+    async fn race_status(
+        &self,
+        ctx: &Context<'_>,
+        athletes: Option<Vec<AthleteId>>,
+    ) -> Result<impl Stream<Item = RaceStatus>> {
+        let updates = proxy(ctx)?.subscribe();
+        let filter = Filter(athletes.map(HashSet::from_iter));
+        Ok(futures_util::stream::unfold(
+            (updates, filter),
+            |(mut updates, filter)| async move {
+                loop {
+                    if let Update::Status { status } = &*push::next(&mut updates, &filter).await? {
+                        return Some((status.clone(), (updates, filter)));
+                    }
+                }
+            },
+        ))
+    }
+}
+
+pub(super) async fn graphiql() -> Html<String> {
+    Html(
+        GraphiQLSource::build()
+            .endpoint("/graphql")
+            .subscription_endpoint("/graphql/ws")
+            .finish(),
+    )
+}
+
+pub(super) async fn query(
+    State(proxy): State<Arc<Proxy>>,
+    Json(request): Json<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    let schema = proxy.schema.clone();
+    Json(schema.execute(request.data(proxy)).await)
+}
+
+pub(super) async fn websocket(
+    upgrade: WebSocketUpgrade,
+    headers: HeaderMap,
+    State(proxy): State<Arc<Proxy>>,
+) -> Response {
+    let protocol = headers
+        .get(SEC_WEBSOCKET_PROTOCOL)
+        .and_then(|protocols| protocols.to_str().ok())
+        .and_then(|protocols| {
+            protocols
+                .split(',')
+                .find_map(|protocol| protocol.trim().parse::<WebSocketProtocols>().ok())
+        });
+    let Some(protocol) = protocol else {
+        return (
+            StatusCode::BAD_REQUEST,
+            "expected the graphql-transport-ws or graphql-ws protocol",
+        )
+            .into_response();
+    };
+    upgrade
+        .protocols(ALL_WEBSOCKET_PROTOCOLS)
+        .on_upgrade(move |socket| async move {
+            let (mut sink, stream) = socket.split();
+            let input = stream
+                .take_while(|message| future::ready(message.is_ok()))
+                .filter_map(|message| {
+                    future::ready(match message {
+                        Ok(Message::Text(text)) => Some(text.into_bytes()),
+                        Ok(Message::Binary(bytes)) => Some(bytes),
+                        _ => None,
+                    })
+                });
+            let mut data = Data::default();
+            data.insert(proxy.clone());
+            let mut output =
+                WebSocket::new(proxy.schema.clone(), input, protocol).connection_data(data);
+            while let Some(message) = output.next().await {
+                let message = match message {
+                    WsMessage::Text(text) => Message::Text(text),
+                    WsMessage::Close(code, reason) => Message::Close(Some(CloseFrame {
+                        code,
+                        reason: reason.into(),
+                    })),
+                };
+                if sink.send(message).await.is_err() {
+                    return;
+                }
+            }
+        })
+}
+
+#[cfg(test)]
+mod test {
+    use crate::{
+        server::{Proxy, ProxyConfig},
+        test_util::MockServer,
+        AthleteStatus, RaceStatus,
+    };
+    use futures_util::StreamExt;
+    use serde_json::json;
+    use std::{sync::Arc, time::Duration};
+
+    #[tokio::test]
+    async fn nested_query() {
+        let upstream = MockServer::start().await;
+        let proxy = Arc::new(Proxy::new(upstream.client(), ProxyConfig::default()).unwrap());
+        let request = async_graphql::Request::new(
+            r#"{
+                athletes {
+                    athleteId
+                    countryCode
+                    status { status distanceToGoal }
+                    trackPoints(last: 2) { timestamp }
+                    nextTurnpoint { id }
+                }
+                replayAt(at: 1686736800) { timestamp status { athlete { lastname } } }
+            }"#,
+        );
+        let response = super::schema().execute(request.data(proxy)).await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        let data = response.data.into_json().unwrap();
+        let maurer = data["athletes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|athlete| athlete["athleteId"] == "29")
+            .unwrap();
+        assert_eq!(maurer["countryCode"], "CH");
+        assert_eq!(
+            maurer["status"],
+            json!({ "status": "FLY", "distanceToGoal": 689.4 })
+        );
+        assert_eq!(maurer["trackPoints"].as_array().unwrap().len(), 2);
+        assert_eq!(maurer["nextTurnpoint"]["id"], "tp9");
+        assert_eq!(data["replayAt"]["timestamp"], 1686736800);
+        assert!(data["replayAt"]["status"][0]["athlete"]["lastname"].is_string());
+
+        // Only athlete 29 has a recorded track upstream.
+        assert!(data["athletes"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .filter(|athlete| athlete["athleteId"] != "29")
+            .all(|athlete| athlete["trackPoints"] == json!([])));
+    }
+
+    #[tokio::test]
+    async fn status_subscription() {
+        let upstream = MockServer::start().await;
+        let proxy = Arc::new(Proxy::new(upstream.client(), ProxyConfig::default()).unwrap());
+        let request = async_graphql::Request::new(
+            r#"subscription { raceStatus(athletes: ["3"]) { athleteId status } }"#,
+        );
+        let mut stream = super::schema().execute_stream(request.data(proxy.clone()));
+
+        let mut status: Vec<RaceStatus> =
+            serde_json::from_slice(crate::test_util::fixture("/race/race-status").unwrap())
+                .unwrap();
+        status[1].status = AthleteStatus::Fly;
+        let publish = async {
+            // Subscribe before publishing.
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            for status in status {
+                proxy.publish(crate::server::Update::Status { status });
+            }
+        };
+        let (response, ()) = tokio::join!(stream.next(), publish);
+        assert_eq!(
+            response.unwrap().data.into_json().unwrap(),
+            json!({ "raceStatus": { "athleteId": "3", "status": "FLY" } })
+        );
+    }
+}
@@ -0,0 +1,480 @@
+//! HTTP proxy that fetches the feeds through one cached [`Client`] and
+//! re-exposes them as plain JSON, so clients don't each scrape the upstream.
+//!
+//! | Endpoint | Response |
+//! |---|---|
+//! | `GET /athletes` | [`Athlete`]s of the overview |
+//! | `GET /turnpoints` | [`TurnPoint`]s of the overview |
+//! | `GET /status` | current [`RaceStatus`] of every athlete |
+//! | `GET /athletes/{id}/track?since=` | latest [`Track`], only points after `since` |
+//! | `GET /replay?at=` | the [`RaceStatusReplay`] snapshot in effect at `at` |
+//! | `GET /updates?athletes=` | Server-Sent Events of [`Update`]s about the athletes, or everyone |
+//! | `GET /metrics` | Prometheus metrics, with the `metrics` feature and [`Proxy::with_metrics`] |
+//! | `GET /updates/ws?athletes=` | the same over a WebSocket, send `{"athletes": [..]}` to change the filter |
+//! | `POST /graphql` | GraphQL queries, with the `graphql` feature, see [`graphql`] |
+//! | `GET /graphql/ws` | GraphQL subscriptions over a WebSocket |
+//!
+//! Times in queries are Unix seconds or RFC 3339. Errors are answered as
+//! `{"error": "..."}` with 400 for bad queries, 404 if upstream has no such
+//! data and 502 for any other upstream failure.
+
+mod cache;
+#[cfg(feature = "graphql")]
+pub mod graphql;
+mod push;
+
+#[cfg(feature = "metrics")]
+use crate::metrics::Metrics;
+use crate::{
+    Athlete, AthleteId, Client, Error, Overview, RaceStatus, RaceStatusReplay, Track, TurnPoint,
+};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, Method, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Json, Router,
+};
+use cache::TtlCache;
+use chrono::{DateTime, Duration as ChronoDuration, Local, NaiveDate, TimeZone, Utc};
+use serde::Deserialize;
+use serde_json::json;
+use std::{net::SocketAddr, sync::Arc, time::Duration};
+use tokio::sync::broadcast;
+use tower_http::cors::{Any, CorsLayer};
+
+pub use push::{PollError, Update};
+
+#[derive(Debug, Clone)]
+pub struct ProxyConfig {
+    /// How long the overview (athletes and turnpoints) and replays of past days are cached.
+    pub overview_ttl: Duration,
+    /// How long the race status is cached.
+    pub status_ttl: Duration,
+    /// How long every athlete's latest track is cached.
+    pub track_ttl: Duration,
+    /// How often upstream is polled for updates to push to subscribers.
+    pub push_interval: Duration,
+}
+
+impl ProxyConfig {
+    /// Fails if a ttl or the push interval is zero.
+    pub fn validate(&self) -> Result<(), Error> {
+        for (name, duration) in [
+            ("overview ttl", self.overview_ttl),
+            ("status ttl", self.status_ttl),
+            ("track ttl", self.track_ttl),
+            ("push interval", self.push_interval),
+        ] {
+            if duration.is_zero() {
+                return Err(Error::InvalidConfig(format!("{} must not be zero", name)));
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Default for ProxyConfig {
+    fn default() -> Self {
+        ProxyConfig {
+            overview_ttl: Duration::from_secs(10 * 60),
+            status_ttl: Duration::from_secs(30),
+            track_ttl: Duration::from_secs(60),
+            push_interval: Duration::from_secs(30),
+        }
+    }
+}
+
+type PollObserver = Box<dyn Fn(&PollError) + Send + Sync>;
+
+pub struct Proxy {
+    client: Client,
+    overview: TtlCache<(), Overview>,
+    status: TtlCache<(), Vec<RaceStatus>>,
+    tracks: TtlCache<AthleteId, Track>,
+    replays: TtlCache<NaiveDate, Vec<RaceStatusReplay>>,
+    past_replays: TtlCache<NaiveDate, Vec<RaceStatusReplay>>,
+    push_interval: Duration,
+    updates: broadcast::Sender<Arc<Update>>,
+    on_poll_error: Option<PollObserver>,
+    #[cfg(feature = "metrics")]
+    metrics: Option<Arc<Metrics>>,
+    #[cfg(feature = "graphql")]
+    schema: graphql::ProxySchema,
+}
+
+impl Proxy {
+    /// Fails if the config is [invalid](ProxyConfig::validate).
+    pub fn new(client: Client, config: ProxyConfig) -> Result<Proxy, Error> {
+        config.validate()?;
+        Ok(Proxy {
+            client,
+            overview: TtlCache::new(config.overview_ttl),
+            status: TtlCache::new(config.status_ttl),
+            tracks: TtlCache::new(config.track_ttl),
+            // Only today's replay still changes, as often as the status; past days are final.
+            replays: TtlCache::new(config.status_ttl),
+            past_replays: TtlCache::new(config.overview_ttl),
+            push_interval: config.push_interval,
+            updates: broadcast::channel(1024).0,
+            on_poll_error: None,
+            #[cfg(feature = "metrics")]
+            metrics: None,
+            #[cfg(feature = "graphql")]
+            schema: graphql::schema(),
+        })
+    }
+
+    /// Calls `observer` with every failed poll for updates, which are otherwise
+    /// skipped silently and retried on the next poll.
+    pub fn on_poll_error(mut self, observer: impl Fn(&PollError) + Send + Sync + 'static) -> Proxy {
+        self.on_poll_error = Some(Box::new(observer));
+        self
+    }
+
+    fn poll_failed(&self, error: PollError) {
+        if let Some(observer) = &self.on_poll_error {
+            observer(&error);
+        }
+    }
+
+    /// Serves `metrics` on `/metrics`. Install them as the client's
+    /// [`observer`](crate::ClientBuilder::observer) too, to include feed health.
+    #[cfg(feature = "metrics")]
+    pub fn with_metrics(mut self, metrics: Arc<Metrics>) -> Proxy {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    /// Receives every update published from now on.
+    pub fn subscribe(&self) -> broadcast::Receiver<Arc<Update>> {
+        self.updates.subscribe()
+    }
+
+    /// Sends the update to every current subscriber.
+    pub fn publish(&self, update: Update) {
+        // Having no subscribers is not an error.
+        let _ = self.updates.send(Arc::new(update));
+    }
+
+    /// Polls upstream every `push_interval` and publishes what changed, forever.
+    pub async fn push_updates(self: Arc<Self>) {
+        let mut poller = push::Poller::default();
+        let mut interval = tokio::time::interval(self.push_interval);
+        loop {
+            interval.tick().await;
+            match poller.poll(&self).await {
+                Ok(updates) => updates.into_iter().for_each(|update| self.publish(update)),
+                Err(err) => self.poll_failed(PollError::Status(err)),
+            }
+        }
+    }
+
+    pub async fn overview(&self) -> Result<Arc<Overview>, Error> {
+        self.overview
+            .get_or_fetch((), Overview::request_with(&self.client))
+            .await
+    }
+
+    pub async fn race_status(&self) -> Result<Arc<Vec<RaceStatus>>, Error> {
+        self.status
+            .get_or_fetch((), RaceStatus::request_with(&self.client))
+            .await
+    }
+
+    /// The latest track, decoded leniently so one incomplete point doesn't
+    /// fail it as a whole, see [`Track::decode_lenient`].
+    pub async fn track(&self, athlete_id: AthleteId) -> Result<Arc<Track>, Error> {
+        self.tracks
+            .get_or_fetch(athlete_id, async {
+                let (mut track, report) =
+                    crate::ApiTrackResponse::request_lenient_with(&self.client, athlete_id).await?;
+                if report.missing_athlete_id {
+                    track.athlete_id = athlete_id;
+                }
+                Ok(track)
+            })
+            .await
+    }
+
+    /// The last race status snapshot taken at or before `at`, looking back one day at most.
+    pub async fn replay_at(&self, at: DateTime<Utc>) -> Result<Option<RaceStatusReplay>, Error> {
+        let day = at.with_timezone(&Local);
+        if let Some(snapshot) = Proxy::snapshot_at(&self.replay(day).await?, at) {
+            return Ok(Some(snapshot));
+        }
+        match self.replay(day - ChronoDuration::days(1)).await {
+            Ok(replay) => Ok(Proxy::snapshot_at(&replay, at)),
+            Err(err) if err.status() == Some(404) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    async fn replay(&self, day: DateTime<Local>) -> Result<Arc<Vec<RaceStatusReplay>>, Error> {
+        let replays = if day.date_naive() < Local::now().date_naive() {
+            &self.past_replays
+        } else {
+            &self.replays
+        };
+        replays
+            .get_or_fetch(
+                day.date_naive(),
+                RaceStatusReplay::request_with(&self.client, day),
+            )
+            .await
+    }
+
+    fn snapshot_at(replay: &[RaceStatusReplay], at: DateTime<Utc>) -> Option<RaceStatusReplay> {
+        replay
+            .iter()
+            .filter(|snapshot| snapshot.timestamp as i64 <= at.timestamp())
+            .max_by_key(|snapshot| snapshot.timestamp)
+            .cloned()
+    }
+
+    pub fn router(self: Arc<Self>) -> Router {
+        let router = Router::new();
+        #[cfg(feature = "metrics")]
+        let router = router.route("/metrics", get(metrics));
+        #[cfg(feature = "graphql")]
+        let router = router
+            .route("/graphql", get(graphql::graphiql).post(graphql::query))
+            .route("/graphql/ws", get(graphql::websocket));
+        router
+            .route("/athletes", get(athletes))
+            .route("/turnpoints", get(turnpoints))
+            .route("/status", get(status))
+            .route("/athletes/:id/track", get(track))
+            .route("/replay", get(replay))
+            .route("/updates", get(push::sse))
+            .route("/updates/ws", get(push::websocket))
+            .layer(
+                CorsLayer::new()
+                    .allow_origin(Any)
+                    .allow_methods([Method::GET, Method::POST])
+                    .allow_headers([header::CONTENT_TYPE]),
+            )
+            .with_state(self)
+    }
+}
+
+/// Serves the proxy on `addr` and pushes updates until the process is stopped.
+pub async fn serve(addr: SocketAddr, proxy: Arc<Proxy>) -> Result<(), Error> {
+    tokio::spawn(proxy.clone().push_updates());
+    axum::Server::try_bind(&addr)?
+        .serve(proxy.router().into_make_service())
+        .await?;
+    Ok(())
+}
+
+enum ApiError {
+    BadRequest(String),
+    NotFound(String),
+    Upstream(Error),
+}
+
+impl From<Error> for ApiError {
+    fn from(err: Error) -> Self {
+        match err.status() {
+            Some(404) => ApiError::NotFound(err.to_string()),
+            _ => ApiError::Upstream(err),
+        }
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, message) = match self {
+            ApiError::BadRequest(message) => (StatusCode::BAD_REQUEST, message),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, message),
+            ApiError::Upstream(err) => (StatusCode::BAD_GATEWAY, err.to_string()),
+        };
+        (status, Json(json!({ "error": message }))).into_response()
+    }
+}
+
+/// Unix seconds or RFC 3339.
+fn parse_time(name: &str, time: &str) -> Result<DateTime<Utc>, ApiError> {
+    let parsed = match time.parse::<f64>() {
+        // `as` would saturate huge values and turn NaN into 1970.
+        Ok(seconds) if !(seconds * 1000.0).is_finite() => None,
+        Ok(seconds) if (seconds * 1000.0).abs() >= i64::MAX as f64 => None,
+        Ok(seconds) => Utc.timestamp_millis_opt((seconds * 1000.0) as i64).single(),
+        Err(_) => DateTime::parse_from_rfc3339(time)
+            .ok()
+            .map(|time| time.with_timezone(&Utc)),
+    };
+    parsed.ok_or_else(|| {
+        ApiError::BadRequest(format!(
+            "{} must be Unix seconds or RFC 3339, got {}",
+            name, time
+        ))
+    })
+}
+
+async fn athletes(State(proxy): State<Arc<Proxy>>) -> Result<Json<Vec<Athlete>>, ApiError> {
+    Ok(Json(proxy.overview().await?.athletes.clone()))
+}
+
+async fn turnpoints(State(proxy): State<Arc<Proxy>>) -> Result<Json<Vec<TurnPoint>>, ApiError> {
+    Ok(Json(proxy.overview().await?.turnpoints.clone()))
+}
+
+async fn status(State(proxy): State<Arc<Proxy>>) -> Result<Json<Vec<RaceStatus>>, ApiError> {
+    Ok(Json(proxy.race_status().await?.as_ref().clone()))
+}
+
+/// Refreshes the race gauges from the cached feeds, best effort so feed
+/// health is still reported while upstream is down. Tracks are only read from
+/// the cache, kept warm by the update poller, so a scrape never fetches them.
+#[cfg(feature = "metrics")]
+async fn metrics(State(proxy): State<Arc<Proxy>>) -> Result<String, ApiError> {
+    let metrics = proxy
+        .metrics
+        .as_ref()
+        .ok_or_else(|| ApiError::NotFound("metrics are not enabled".to_string()))?;
+    if let (Ok(overview), Ok(status)) = (proxy.overview().await, proxy.race_status().await) {
+        let tracks: Vec<Arc<Track>> = status
+            .iter()
+            .filter_map(|status| proxy.tracks.cached(&status.athlete_id))
+            .collect();
+        metrics.observe_race(
+            &overview,
+            &status,
+            tracks.iter().map(|track| track.as_ref()),
+            Utc::now(),
+        );
+    }
+    Ok(metrics.encode())
+}
+
+#[derive(Deserialize)]
+struct TrackQuery {
+    since: Option<String>,
+}
+
+async fn track(
+    State(proxy): State<Arc<Proxy>>,
+    Path(athlete_id): Path<String>,
+    Query(query): Query<TrackQuery>,
+) -> Result<Json<Track>, ApiError> {
+    let athlete_id = athlete_id
+        .parse()
+        .map_err(|_| ApiError::BadRequest(format!("invalid athlete id {}", athlete_id)))?;
+    let since = query
+        .since
+        .map(|since| parse_time("since", &since))
+        .transpose()?;
+    let mut track = proxy.track(athlete_id).await?.as_ref().clone();
+    if let Some(since) = since {
+        let since = since.timestamp_millis() as f64 / 1000.0;
+        track.points.retain(|point| point.timestamp > since);
+    }
+    Ok(Json(track))
+}
+
+#[derive(Deserialize)]
+struct ReplayQuery {
+    at: String,
+}
+
+async fn replay(
+    State(proxy): State<Arc<Proxy>>,
+    Query(query): Query<ReplayQuery>,
+) -> Result<Json<RaceStatusReplay>, ApiError> {
+    let at = parse_time("at", &query.at)?;
+    proxy
+        .replay_at(at)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("no race status before {}", at.to_rfc3339())))
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Proxy, ProxyConfig};
+    use crate::{
+        test_util::{self, MockServer},
+        RaceStatusReplay, Track,
+    };
+    use std::{net::SocketAddr, sync::Arc, time::Duration};
+
+    async fn start(upstream: &MockServer) -> SocketAddr {
+        let proxy = Arc::new(Proxy::new(upstream.client(), ProxyConfig::default()).unwrap());
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
+            .serve(proxy.router().into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+        addr
+    }
+
+    async fn get(addr: SocketAddr, path: &str) -> reqwest::Response {
+        reqwest::get(format!("http://{}{}", addr, path))
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn zero_ttl() {
+        let config = ProxyConfig {
+            overview_ttl: Duration::ZERO,
+            ..ProxyConfig::default()
+        };
+        assert!(Proxy::new(crate::Client::shared().clone(), config).is_err());
+    }
+
+    #[tokio::test]
+    async fn endpoints() {
+        let upstream = MockServer::start().await;
+        let addr = start(&upstream).await;
+
+        let athletes: serde_json::Value = get(addr, "/athletes").await.json().await.unwrap();
+        assert_eq!(athletes.as_array().unwrap().len(), 3);
+        let turnpoints: serde_json::Value = get(addr, "/turnpoints").await.json().await.unwrap();
+        assert_eq!(turnpoints.as_array().unwrap().len(), 4);
+        assert_eq!(upstream.hits(crate::test_util::OVERVIEW_PATH), 1);
+
+        let response = get(addr, "/status").await;
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+        let status: serde_json::Value = response.json().await.unwrap();
+        assert_eq!(status.as_array().unwrap().len(), 3);
+
+        let track: Track = get(addr, "/athletes/29/track?since=2023-06-14T16:49:00Z")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(track.points.len(), 10);
+        assert_eq!(get(addr, "/athletes/3/track").await.status(), 404);
+        assert_eq!(
+            get(addr, "/athletes/29/track?since=soon").await.status(),
+            400
+        );
+        for at in ["nan", "inf", "1e30"] {
+            let path = format!("/replay?at={}", at);
+            assert_eq!(get(addr, &path).await.status(), 400, "{}", at);
+        }
+
+        let replay: RaceStatusReplay = get(addr, "/replay?at=2023-06-14T11:00:00Z")
+            .await
+            .json()
+            .await
+            .unwrap();
+        assert_eq!(replay.status.len(), 3);
+        assert_eq!(replay.timestamp, 1686736800);
+    }
+
+    #[tokio::test]
+    async fn incomplete_track() {
+        let upstream = MockServer::start().await;
+        upstream.route(
+            "/race/athlete/29/track/latest.pbf",
+            test_util::incomplete_track(),
+        );
+        let addr = start(&upstream).await;
+
+        let track: Track = get(addr, "/athletes/29/track").await.json().await.unwrap();
+        assert_eq!(track.points.len(), 60);
+        assert_eq!(track.points[0].altitude_agl, 0.0);
+    }
+}
@@ -0,0 +1,357 @@
+//! Deltas pushed to subscribers over WebSocket (`/updates/ws`) and
+//! Server-Sent Events (`/updates`), so clients don't have to poll.
+//!
+//! Both take `?athletes=29,3` to only receive updates about those athletes.
+//! WebSocket clients can change their filter at any time by sending
+//! `{"athletes": ["29", "3"]}`, or `{"athletes": null}` for everyone.
+
+use super::Proxy;
+use crate::{
+    events::{EventDetector, RaceEvent},
+    AthleteId, Error, RaceStatus, TrackPoint,
+};
+use axum::{
+    extract::{
+        ws::{Message, WebSocket, WebSocketUpgrade},
+        Query, State,
+    },
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Response,
+    },
+};
+use futures_util::Stream;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{HashMap, HashSet},
+    fmt,
+    sync::Arc,
+};
+use tokio::sync::broadcast::{error::RecvError, Receiver};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum Update {
+    /// Points of the athlete's track newer than any sent before.
+    #[serde(rename_all = "camelCase")]
+    TrackPoints {
+        athlete_id: AthleteId,
+        points: Vec<TrackPoint>,
+    },
+    /// The athlete's race status changed.
+    Status {
+        status: RaceStatus,
+    },
+    Event {
+        event: RaceEvent,
+    },
+}
+
+impl Update {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Update::TrackPoints { .. } => "trackPoints",
+            Update::Status { .. } => "status",
+            Update::Event { .. } => "event",
+        }
+    }
+
+    pub fn concerns(&self, athlete_id: AthleteId) -> bool {
+        match self {
+            Update::TrackPoints { athlete_id: id, .. } => *id == athlete_id,
+            Update::Status { status } => status.athlete_id == athlete_id,
+            Update::Event { event } => event.concerns(athlete_id),
+        }
+    }
+}
+
+/// A failed poll for updates, see [`Proxy::on_poll_error`].
+#[derive(Debug)]
+pub enum PollError {
+    /// The overview or race status could not be fetched, nothing was published.
+    Status(Error),
+    /// An athlete's track could not be fetched, its points are published on a later poll.
+    Track { athlete_id: AthleteId, error: Error },
+}
+
+impl fmt::Display for PollError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PollError::Status(err) => write!(f, "failed to poll for updates: {}", err),
+            PollError::Track { athlete_id, error } => write!(
+                f,
+                "failed to poll track of athlete {}: {}",
+                athlete_id, error
+            ),
+        }
+    }
+}
+
+/// Athletes a subscriber wants updates about, everyone if `None`.
+pub(super) struct Filter(pub(super) Option<HashSet<AthleteId>>);
+
+impl Filter {
+    fn matches(&self, update: &Update) -> bool {
+        match &self.0 {
+            Some(athletes) => athletes.iter().any(|&athlete| update.concerns(athlete)),
+            None => true,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+pub(super) struct SubscriptionQuery {
+    /// Comma separated athlete ids, invalid ones are ignored.
+    athletes: Option<String>,
+}
+
+impl SubscriptionQuery {
+    fn filter(&self) -> Filter {
+        Filter(self.athletes.as_ref().map(|athletes| {
+            athletes
+                .split(',')
+                .filter_map(|athlete| athlete.trim().parse().ok())
+                .collect()
+        }))
+    }
+}
+
+#[derive(Deserialize)]
+struct Subscribe {
+    athletes: Option<Vec<AthleteId>>,
+}
+
+/// What was last seen upstream, to turn polls into deltas.
+#[derive(Default)]
+pub(super) struct Poller {
+    detector: Option<EventDetector>,
+    status: HashMap<AthleteId, RaceStatus>,
+    last_point: HashMap<AthleteId, f64>,
+}
+
+impl Poller {
+    /// Updates since the previous poll. The first poll only sets the baseline.
+    pub(super) async fn poll(&mut self, proxy: &Proxy) -> Result<Vec<Update>, Error> {
+        let status = proxy.race_status().await?;
+        let detector = match &mut self.detector {
+            Some(detector) => detector,
+            None => self
+                .detector
+                .insert(EventDetector::new(&*proxy.overview().await?)),
+        };
+        let baseline = self.status.is_empty();
+        let mut updates = Vec::new();
+        for status in status.iter() {
+            if self.status.get(&status.athlete_id) != Some(status) {
+                self.status.insert(status.athlete_id, status.clone());
+                if !baseline {
+                    updates.push(Update::Status {
+                        status: status.clone(),
+                    });
+                }
+            }
+        }
+        updates.extend(
+            detector
+                .update(&status)
+                .into_iter()
+                .map(|event| Update::Event { event }),
+        );
+
+        for status in status.iter() {
+            let track = match proxy.track(status.athlete_id).await {
+                Ok(track) => track,
+                Err(error) => {
+                    proxy.poll_failed(PollError::Track {
+                        athlete_id: status.athlete_id,
+                        error,
+                    });
+                    continue;
+                }
+            };
+            let last = self.last_point.get(&status.athlete_id).copied();
+            let points: Vec<TrackPoint> = track
+                .points
+                .iter()
+                .filter(|point| !last.is_some_and(|last| point.timestamp <= last))
+                .cloned()
+                .collect();
+            let Some(newest) = points.last() else {
+                continue;
+            };
+            self.last_point.insert(status.athlete_id, newest.timestamp);
+            if last.is_some() {
+                updates.push(Update::TrackPoints {
+                    athlete_id: status.athlete_id,
+                    points,
+                });
+            }
+        }
+        Ok(updates)
+    }
+}
+
+/// Next update matching the filter, `None` once the proxy is gone.
+pub(super) async fn next(
+    updates: &mut Receiver<Arc<Update>>,
+    filter: &Filter,
+) -> Option<Arc<Update>> {
+    loop {
+        match updates.recv().await {
+            Ok(update) if filter.matches(&update) => return Some(update),
+            Ok(_) | Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => return None,
+        }
+    }
+}
+
+pub(super) async fn sse(
+    State(proxy): State<Arc<Proxy>>,
+    Query(query): Query<SubscriptionQuery>,
+) -> Sse<impl Stream<Item = Result<Event, serde_json::Error>>> {
+    let stream = futures_util::stream::unfold(
+        (proxy.subscribe(), query.filter()),
+        |(mut updates, filter)| async move {
+            let update = next(&mut updates, &filter).await?;
+            let event = Event::default().event(update.kind()).json_data(&*update);
+            Some((event, (updates, filter)))
+        },
+    );
+    Sse::new(stream).keep_alive(KeepAlive::default())
+}
+
+pub(super) async fn websocket(
+    upgrade: WebSocketUpgrade,
+    State(proxy): State<Arc<Proxy>>,
+    Query(query): Query<SubscriptionQuery>,
+) -> Response {
+    let updates = proxy.subscribe();
+    upgrade.on_upgrade(move |socket| push(socket, updates, query.filter()))
+}
+
+async fn push(mut socket: WebSocket, mut updates: Receiver<Arc<Update>>, mut filter: Filter) {
+    loop {
+        tokio::select! {
+            update = next(&mut updates, &filter) => {
+                let Some(update) = update else {
+                    return;
+                };
+                let Ok(text) = serde_json::to_string(&*update) else {
+                    continue;
+                };
+                if socket.send(Message::Text(text)).await.is_err() {
+                    return;
+                }
+            }
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => match serde_json::from_str::<Subscribe>(&text) {
+                    Ok(subscribe) => filter = Filter(subscribe.athletes.map(HashSet::from_iter)),
+                    Err(err) => {
+                        let error = serde_json::json!({ "error": err.to_string() }).to_string();
+                        if socket.send(Message::Text(error)).await.is_err() {
+                            return;
+                        }
+                    }
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => return,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{Poller, Update};
+    use crate::{
+        server::{Proxy, ProxyConfig},
+        test_util::MockServer,
+        AthleteId, AthleteStatus, RaceStatus,
+    };
+    use std::{sync::Arc, time::Duration};
+
+    fn proxy(upstream: &MockServer) -> Arc<Proxy> {
+        Arc::new(
+            Proxy::new(
+                upstream.client(),
+                ProxyConfig {
+                    status_ttl: Duration::from_nanos(1),
+                    track_ttl: Duration::from_nanos(1),
+                    ..ProxyConfig::default()
+                },
+            )
+            .unwrap(),
+        )
+    }
+
+    fn take_off(upstream: &MockServer) {
+        let mut status: Vec<RaceStatus> =
+            serde_json::from_slice(crate::test_util::fixture("/race/race-status").unwrap())
+                .unwrap();
+        status[1].status = AthleteStatus::Fly;
+        status[1].timestamp += 60;
+        upstream.route("/race/race-status", serde_json::to_vec(&status).unwrap());
+    }
+
+    #[tokio::test]
+    async fn poll_deltas() {
+        let upstream = MockServer::start().await;
+        let proxy = proxy(&upstream);
+        let mut poller = Poller::default();
+        assert!(poller.poll(&proxy).await.unwrap().is_empty());
+        assert!(poller.poll(&proxy).await.unwrap().is_empty());
+
+        take_off(&upstream);
+        let updates = poller.poll(&proxy).await.unwrap();
+        let kinds: Vec<&str> = updates.iter().map(Update::kind).collect();
+        assert_eq!(kinds, ["status", "event"]);
+        assert!(updates
+            .iter()
+            .all(|update| update.concerns(AthleteId::new(3))));
+    }
+
+    #[tokio::test]
+    async fn push_to_subscribers() {
+        use futures_util::{SinkExt, StreamExt};
+        use tokio_tungstenite::tungstenite::Message;
+
+        let upstream = MockServer::start().await;
+        let proxy = proxy(&upstream);
+        let server = axum::Server::bind(&"127.0.0.1:0".parse().unwrap())
+            .serve(proxy.clone().router().into_make_service());
+        let addr = server.local_addr();
+        tokio::spawn(server);
+
+        let mut sse = reqwest::get(format!("http://{}/updates?athletes=3", addr))
+            .await
+            .unwrap();
+        let (mut ws, _) = tokio_tungstenite::connect_async(format!("ws://{}/updates/ws", addr))
+            .await
+            .unwrap();
+        ws.send(Message::Text(r#"{"athletes": ["12"]}"#.into()))
+            .await
+            .unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+
+        let mut poller = Poller::default();
+        poller.poll(&proxy).await.unwrap();
+        take_off(&upstream);
+        for update in poller.poll(&proxy).await.unwrap() {
+            proxy.publish(update);
+        }
+        proxy.publish(Update::TrackPoints {
+            athlete_id: AthleteId::new(12),
+            points: Vec::new(),
+        });
+
+        let chunk = sse.chunk().await.unwrap().unwrap();
+        let chunk = String::from_utf8_lossy(&chunk);
+        assert!(chunk.starts_with("event:status\n"), "{}", chunk);
+        assert!(chunk.contains(r#""athleteId":"3""#));
+
+        let message = ws.next().await.unwrap().unwrap();
+        let update: serde_json::Value = serde_json::from_str(message.to_text().unwrap()).unwrap();
+        assert_eq!(update["type"], "trackPoints");
+        assert_eq!(update["athleteId"], "12");
+    }
+}
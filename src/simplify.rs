@@ -0,0 +1,383 @@
+//! Simplification, resampling and segmentation of [`Track`]s, e.g. for maps
+//! at any zoom level.
+//!
+//! Distances are measured on local tangent planes, see [`LocalFrame`], so
+//! tolerances in metres hold anywhere along the race. Points are expected in
+//! time order, as the feeds send them.
+
+use crate::{geo::LocalFrame, LatLng, Track, TrackPoint};
+use std::{
+    cmp::{Ordering, Reverse},
+    collections::BinaryHeap,
+    time::Duration,
+};
+
+/// Metres east and north of the frame's origin.
+fn planar(frame: &LocalFrame, point: &TrackPoint) -> (f64, f64) {
+    let enu = frame.to_enu(LatLng::from(point), 0.0);
+    (enu.east, enu.north)
+}
+
+/// Distance in metres from `point` to the segment from `start` to `end`.
+fn distance_to_segment(point: &TrackPoint, start: &TrackPoint, end: &TrackPoint) -> f64 {
+    let frame = LocalFrame::new(LatLng::from(start), 0.0);
+    let (px, py) = planar(&frame, point);
+    let (dx, dy) = planar(&frame, end);
+    let length = dx * dx + dy * dy;
+    let t = if length == 0.0 {
+        0.0
+    } else {
+        ((px * dx + py * dy) / length).clamp(0.0, 1.0)
+    };
+    (px - t * dx).hypot(py - t * dy)
+}
+
+/// Area in square metres of the triangle of three points.
+fn triangle_area(a: &TrackPoint, b: &TrackPoint, c: &TrackPoint) -> f64 {
+    let frame = LocalFrame::new(LatLng::from(b), 0.0);
+    let (ax, ay) = planar(&frame, a);
+    let (cx, cy) = planar(&frame, c);
+    (ax * cy - cx * ay).abs() / 2.0
+}
+
+/// Effective area of a point in the Visvalingam heap, smallest first.
+#[derive(PartialEq)]
+struct Area(f64, usize);
+
+impl Eq for Area {}
+
+impl PartialOrd for Area {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Area {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.0.total_cmp(&other.0).then(self.1.cmp(&other.1))
+    }
+}
+
+fn lerp(a: f32, b: f32, t: f64) -> f32 {
+    (f64::from(a) + (f64::from(b) - f64::from(a)) * t) as f32
+}
+
+impl Track {
+    fn with_points(&self, points: Vec<TrackPoint>) -> Track {
+        Track {
+            athlete_id: self.athlete_id,
+            points,
+        }
+    }
+
+    fn keep(&self, keep: &[bool]) -> Track {
+        self.with_points(
+            self.points
+                .iter()
+                .zip(keep)
+                .filter(|(_, keep)| **keep)
+                .map(|(point, _)| point.clone())
+                .collect(),
+        )
+    }
+
+    /// Ramer–Douglas–Peucker: keeps the first and last points and every point
+    /// needed so that no dropped point is more than `tolerance` metres from
+    /// the simplified line.
+    pub fn simplify_douglas_peucker(&self, tolerance: f64) -> Track {
+        let points = &self.points;
+        if points.len() < 3 {
+            return self.clone();
+        }
+        let mut keep = vec![false; points.len()];
+        keep[0] = true;
+        keep[points.len() - 1] = true;
+        let mut ranges = vec![(0, points.len() - 1)];
+        while let Some((start, end)) = ranges.pop() {
+            let farthest = (start + 1..end)
+                .map(|index| {
+                    let distance =
+                        distance_to_segment(&points[index], &points[start], &points[end]);
+                    (index, distance)
+                })
+                .max_by(|a, b| a.1.total_cmp(&b.1));
+            if let Some((index, distance)) = farthest {
+                if distance > tolerance {
+                    keep[index] = true;
+                    ranges.push((start, index));
+                    ranges.push((index, end));
+                }
+            }
+        }
+        self.keep(&keep)
+    }
+
+    /// Visvalingam–Whyatt: repeatedly drops the point that forms the smallest
+    /// triangle with its neighbours, while that triangle is smaller than a
+    /// square of `tolerance` metres. Keeps the first and last points.
+    pub fn simplify_visvalingam(&self, tolerance: f64) -> Track {
+        let points = &self.points;
+        let len = points.len();
+        if len < 3 {
+            return self.clone();
+        }
+        let threshold = tolerance * tolerance;
+        let mut previous: Vec<usize> = (0..len).map(|index| index.saturating_sub(1)).collect();
+        let mut next: Vec<usize> = (1..=len).collect();
+        let mut areas = vec![f64::INFINITY; len];
+        let mut heap = BinaryHeap::new();
+        for index in 1..len - 1 {
+            areas[index] = triangle_area(&points[index - 1], &points[index], &points[index + 1]);
+            heap.push(Reverse(Area(areas[index], index)));
+        }
+        let mut keep = vec![true; len];
+        while let Some(Reverse(Area(area, index))) = heap.pop() {
+            // Entries of dropped points and outdated areas are skipped.
+            if !keep[index] || area != areas[index] {
+                continue;
+            }
+            if area >= threshold {
+                break;
+            }
+            keep[index] = false;
+            let (before, after) = (previous[index], next[index]);
+            next[before] = after;
+            previous[after] = before;
+            // A neighbour's area never drops below the one just removed, so
+            // points are removed in order of significance.
+            for neighbour in [before, after] {
+                if neighbour == 0 || neighbour == len - 1 {
+                    continue;
+                }
+                let updated = triangle_area(
+                    &points[previous[neighbour]],
+                    &points[neighbour],
+                    &points[next[neighbour]],
+                )
+                .max(area);
+                areas[neighbour] = updated;
+                heap.push(Reverse(Area(updated, neighbour)));
+            }
+        }
+        self.keep(&keep)
+    }
+
+    /// A point every `interval`, at multiples of it since the Unix epoch,
+    /// between the first and last point. Position, altitudes and speeds are
+    /// interpolated linearly, the status is the one of the point before.
+    ///
+    /// Interpolates across gaps too; split the track with [`Track::segments`]
+    /// first to leave them empty.
+    pub fn resample(&self, interval: Duration) -> Track {
+        let step = interval.as_secs_f64();
+        let (Some(first), Some(last)) = (self.points.first(), self.points.last()) else {
+            return self.clone();
+        };
+        if step <= 0.0 {
+            return self.clone();
+        }
+        let mut points = Vec::new();
+        let mut index = 0;
+        let mut slot = (first.timestamp / step).ceil();
+        while slot * step <= last.timestamp {
+            let timestamp = slot * step;
+            while index + 1 < self.points.len() && self.points[index + 1].timestamp <= timestamp {
+                index += 1;
+            }
+            let before = &self.points[index];
+            let point = match self.points.get(index + 1) {
+                Some(after) if before.timestamp < timestamp => {
+                    let t = (timestamp - before.timestamp) / (after.timestamp - before.timestamp);
+                    TrackPoint {
+                        timestamp,
+                        lat: lerp(before.lat, after.lat, t),
+                        lng: lerp(before.lng, after.lng, t),
+                        altitude: lerp(before.altitude, after.altitude, t),
+                        altitude_agl: lerp(before.altitude_agl, after.altitude_agl, t),
+                        status: before.status.clone(),
+                        speed: lerp(before.speed, after.speed, t),
+                        vertical_speed: lerp(before.vertical_speed, after.vertical_speed, t),
+                    }
+                }
+                _ => TrackPoint {
+                    timestamp,
+                    ..before.clone()
+                },
+            };
+            points.push(point);
+            slot += 1.0;
+        }
+        self.with_points(points)
+    }
+
+    /// The track split wherever consecutive points are more than `max_gap`
+    /// apart, e.g. while the tracker was off. Empty for a track without points.
+    pub fn segments(&self, max_gap: Duration) -> Vec<Track> {
+        let max_gap = max_gap.as_secs_f64();
+        let mut segments: Vec<Track> = Vec::new();
+        let mut previous: Option<f64> = None;
+        for point in &self.points {
+            match (segments.last_mut(), previous) {
+                (Some(segment), Some(previous)) if point.timestamp - previous <= max_gap => {
+                    segment.points.push(point.clone());
+                }
+                _ => segments.push(self.with_points(vec![point.clone()])),
+            }
+            previous = Some(point.timestamp);
+        }
+        segments
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::distance_to_segment;
+    use crate::{test_util, ApiTrackResponse, AthleteId, LatLng, Track, TrackPoint};
+    use std::time::Duration;
+
+    fn latest() -> Track {
+        let response: ApiTrackResponse = protobuf::Message::parse_from_bytes(
+            test_util::fixture("/race/athlete/29/track/latest.pbf").unwrap(),
+        )
+        .unwrap();
+        Track::from(response)
+    }
+
+    fn point(timestamp: f64, position: LatLng) -> TrackPoint {
+        TrackPoint {
+            timestamp,
+            lat: position.lat as f32,
+            lng: position.lng as f32,
+            altitude: 1000.0,
+            altitude_agl: 100.0,
+            status: Some("FLY".to_string()),
+            speed: 36.0,
+            vertical_speed: 0.0,
+        }
+    }
+
+    /// Zigzags 100 m either side of a line north, a point every 500 m.
+    fn zigzag() -> Track {
+        let start = LatLng::new(46.0, 8.0);
+        Track {
+            athlete_id: AthleteId::new(29),
+            points: (0..11)
+                .map(|index| {
+                    let along = start.destination(0.0, 500.0 * index as f64);
+                    let side = if index % 2 == 0 { 90.0 } else { 270.0 };
+                    let offset = if index % 10 == 0 { 0.0 } else { 100.0 };
+                    point(index as f64 * 60.0, along.destination(side, offset))
+                })
+                .collect(),
+        }
+    }
+
+    fn is_subsequence(simplified: &Track, track: &Track) -> bool {
+        let mut points = track.points.iter();
+        simplified
+            .points
+            .iter()
+            .all(|kept| points.any(|point| point == kept))
+    }
+
+    #[test]
+    fn douglas_peucker() {
+        let zigzag = zigzag();
+        assert_eq!(zigzag.simplify_douglas_peucker(150.0).points.len(), 2);
+        assert_eq!(zigzag.simplify_douglas_peucker(50.0), zigzag);
+
+        let track = latest();
+        let simplified = track.simplify_douglas_peucker(50.0);
+        assert!(simplified.points.len() < track.points.len());
+        assert!(is_subsequence(&simplified, &track));
+        assert_eq!(simplified.points.first(), track.points.first());
+        assert_eq!(simplified.points.last(), track.points.last());
+        // Every dropped point is within the tolerance of the simplified line.
+        for point in &track.points {
+            let distance = simplified
+                .points
+                .windows(2)
+                .filter(|pair| (pair[0].timestamp..=pair[1].timestamp).contains(&point.timestamp))
+                .map(|pair| distance_to_segment(point, &pair[0], &pair[1]))
+                .fold(f64::INFINITY, f64::min);
+            assert!(distance <= 50.0, "{}", distance);
+        }
+    }
+
+    #[test]
+    fn visvalingam() {
+        let zigzag = zigzag();
+        // The smallest triangles, next to the ends, have 75 000 m².
+        assert_eq!(zigzag.simplify_visvalingam(250.0), zigzag);
+        // No triangle in the 200 m wide, 5 km long strip reaches 1 km².
+        assert_eq!(zigzag.simplify_visvalingam(1000.0).points.len(), 2);
+
+        let track = latest();
+        let coarse = track.simplify_visvalingam(100.0);
+        let fine = track.simplify_visvalingam(10.0);
+        assert!(coarse.points.len() < fine.points.len());
+        assert!(fine.points.len() <= track.points.len());
+        assert!(is_subsequence(&coarse, &track));
+        assert_eq!(coarse.points.first(), track.points.first());
+        assert_eq!(coarse.points.last(), track.points.last());
+    }
+
+    #[test]
+    fn resample() {
+        let start = LatLng::new(46.0, 8.0);
+        let mut before = point(1000.0, start);
+        before.status = Some("HIKE".to_string());
+        let mut after = point(1100.0, LatLng::new(46.001, 8.002));
+        after.altitude = 1100.0;
+        after.speed = 46.0;
+        let track = Track {
+            athlete_id: AthleteId::new(29),
+            points: vec![before, after],
+        };
+
+        let resampled = track.resample(Duration::from_secs(30));
+        let timestamps: Vec<f64> = resampled.points.iter().map(|p| p.timestamp).collect();
+        assert_eq!(timestamps, [1020.0, 1050.0, 1080.0]);
+        let middle = &resampled.points[1];
+        assert!((middle.lat - 46.0005).abs() < 1e-5 && (middle.lng - 8.001).abs() < 1e-5);
+        assert_eq!((middle.altitude, middle.speed), (1050.0, 41.0));
+        assert_eq!(middle.status.as_deref(), Some("HIKE"));
+
+        let track = latest();
+        let minutes = track.resample(Duration::from_secs(300));
+        assert!(minutes
+            .points
+            .iter()
+            .all(|point| point.timestamp % 300.0 == 0.0));
+        assert_eq!(minutes.points[0], track.points[0]);
+        assert_eq!(track.resample(Duration::ZERO), track);
+    }
+
+    #[test]
+    fn segments() {
+        let start = LatLng::new(46.0, 8.0);
+        let track = Track {
+            athlete_id: AthleteId::new(29),
+            points: [0.0, 60.0, 120.0, 1000.0, 1060.0, 5000.0]
+                .into_iter()
+                .map(|timestamp| point(timestamp, start))
+                .collect(),
+        };
+        let lengths: Vec<usize> = track
+            .segments(Duration::from_secs(300))
+            .iter()
+            .map(|segment| segment.points.len())
+            .collect();
+        assert_eq!(lengths, [3, 2, 1]);
+        assert_eq!(
+            track.segments(Duration::from_secs(5000)),
+            std::slice::from_ref(&track)
+        );
+        assert!(Track {
+            athlete_id: AthleteId::new(29),
+            points: Vec::new(),
+        }
+        .segments(Duration::from_secs(300))
+        .is_empty());
+    }
+}
@@ -1,86 +1,153 @@
 mod overview;
 mod race_status;
+#[allow(renamed_and_removed_lints, unused_parens, mismatched_lifetime_syntaxes)]
 mod track_response;
+mod types;
 
 pub use self::{
-    overview::Overview,
-    race_status::{RaceStatus, RaceStatusReplay},
-    track_response::ApiTrackResponse,
+    overview::{Athlete, DateRange, News, Overview, Sponsor, ThreeDimensionalModels, TurnPoint},
+    race_status::{AthleteStatus, RaceStatus, RaceStatusReplay},
+    track_response::{ApiTrackPoint, ApiTrackResponse},
+    types::{AthleteId, HexColor, ParseAthleteIdError, ParseColorError, TurnPointId},
 };
 
+use crate::{Client, DecodeReport, Error, Track};
 use chrono::{DateTime, Utc};
-use protobuf;
+use protobuf::Message;
 
 impl ApiTrackResponse {
-    pub async fn request(athlete_id: &str) -> Result<ApiTrackResponse, Box<dyn std::error::Error>> {
-        let data = reqwest::get(format!(
-            "https://rbxltdata.redbullxalps.com/race/athlete/{}/track/latest.pbf",
-            athlete_id
-        ))
-        .await?
-        .bytes()
-        .await?
-        .to_vec();
-        Ok(protobuf::Message::parse_from_bytes(&data)?)
+    /// Parses the bytes of a `.pbf` track feed.
+    pub fn decode(bytes: &[u8]) -> Result<ApiTrackResponse, Error> {
+        Ok(ApiTrackResponse::parse_from_bytes(bytes)?)
+    }
+
+    /// Serialises into the `.pbf` feed format, failing if a required field is not set.
+    ///
+    /// Build the response from a [`crate::Track`] with [`crate::Track::to_response`]
+    /// to also check the values of its points.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        Ok(self.write_to_bytes()?)
+    }
+
+    pub async fn request(athlete_id: AthleteId) -> Result<ApiTrackResponse, Error> {
+        ApiTrackResponse::request_with(Client::shared(), athlete_id).await
+    }
+
+    pub async fn request_with(
+        client: &Client,
+        athlete_id: AthleteId,
+    ) -> Result<ApiTrackResponse, Error> {
+        client
+            .get_protobuf(&ApiTrackResponse::latest_url(client, athlete_id))
+            .await
+    }
+
+    /// Like [`ApiTrackResponse::request`], but decodes with
+    /// [`Track::decode_lenient`] so a point lacking a field doesn't fail the
+    /// whole track.
+    pub async fn request_lenient(athlete_id: AthleteId) -> Result<(Track, DecodeReport), Error> {
+        ApiTrackResponse::request_lenient_with(Client::shared(), athlete_id).await
+    }
+
+    pub async fn request_lenient_with(
+        client: &Client,
+        athlete_id: AthleteId,
+    ) -> Result<(Track, DecodeReport), Error> {
+        client
+            .get_track_lenient(&ApiTrackResponse::latest_url(client, athlete_id))
+            .await
     }
 
     /// # Only 5 Minute gaps allowed
     pub async fn request_replay(
-        athlete_id: &str,
+        athlete_id: AthleteId,
         date_time: DateTime<Utc>,
-    ) -> Result<ApiTrackResponse, Box<dyn std::error::Error>> {
-        println!(
-            "{}",
-            format!(
-                "https://rbxltdata.redbullxalps.com/race/athlete/{}/track/latest-replay/{}.pbf",
-                athlete_id,
-                date_time.to_rfc3339()
+    ) -> Result<ApiTrackResponse, Error> {
+        ApiTrackResponse::request_replay_with(Client::shared(), athlete_id, date_time).await
+    }
+
+    /// # Only 5 Minute gaps allowed
+    pub async fn request_replay_with(
+        client: &Client,
+        athlete_id: AthleteId,
+        date_time: DateTime<Utc>,
+    ) -> Result<ApiTrackResponse, Error> {
+        client
+            .get_protobuf(&ApiTrackResponse::replay_url(client, athlete_id, date_time))
+            .await
+    }
+
+    /// Like [`ApiTrackResponse::request_replay_with`], but decodes with
+    /// [`Track::decode_lenient`].
+    pub async fn request_replay_lenient_with(
+        client: &Client,
+        athlete_id: AthleteId,
+        date_time: DateTime<Utc>,
+    ) -> Result<(Track, DecodeReport), Error> {
+        client
+            .get_track_lenient(&ApiTrackResponse::replay_url(client, athlete_id, date_time))
+            .await
+    }
+
+    pub async fn request_reduced(athlete_id: AthleteId) -> Result<ApiTrackResponse, Error> {
+        ApiTrackResponse::request_reduced_with(Client::shared(), athlete_id).await
+    }
+
+    pub async fn request_reduced_with(
+        client: &Client,
+        athlete_id: AthleteId,
+    ) -> Result<ApiTrackResponse, Error> {
+        client
+            .get_protobuf(
+                &client.data_url(&format!("race/athlete/{}/track/reduced.pbf", athlete_id)),
             )
-        );
-        let data = reqwest::get(format!(
-            "https://rbxltdata.redbullxalps.com/race/athlete/{}/track/latest-replay/{}.pbf",
+            .await
+    }
+
+    fn latest_url(client: &Client, athlete_id: AthleteId) -> String {
+        client.data_url(&format!("race/athlete/{}/track/latest.pbf", athlete_id))
+    }
+
+    fn replay_url(client: &Client, athlete_id: AthleteId, date_time: DateTime<Utc>) -> String {
+        client.data_url(&format!(
+            "race/athlete/{}/track/latest-replay/{}.pbf",
             athlete_id,
             date_time.format("%Y-%m-%dT%H:%M:%SZ")
         ))
-        .await?
-        .bytes()
-        .await?
-        .to_vec();
-        Ok(protobuf::Message::parse_from_bytes(&data)?)
-    }
-    pub async fn request_reduced(
-        athlete_id: &str,
-    ) -> Result<ApiTrackResponse, Box<dyn std::error::Error>> {
-        let data = reqwest::get(format!(
-            "https://rbxltdata.redbullxalps.com/race/athlete/{}/track/reduced.pbf",
-            athlete_id
-        ))
-        .await?
-        .bytes()
-        .await?
-        .to_vec();
-        Ok(protobuf::Message::parse_from_bytes(&data)?)
     }
 }
 
 #[cfg(test)]
 mod test {
     use super::ApiTrackResponse;
+    use crate::{
+        test_util::{self, MockServer},
+        AthleteId, Repair,
+    };
     use chrono::{DateTime, TimeZone, Utc};
     #[tokio::test]
     async fn track_response() {
-        let response = ApiTrackResponse::request("29").await.unwrap();
-        println!("{:?}", response);
+        let server = MockServer::start().await;
+        let response = ApiTrackResponse::request_with(&server.client(), AthleteId::new(29))
+            .await
+            .unwrap();
+        assert_eq!(response.get_athlete_id(), 29);
+        assert_eq!(response.get_track_points().len(), 60);
     }
     #[tokio::test]
     async fn track_reduced() {
-        let response = ApiTrackResponse::request_reduced("29").await.unwrap();
-        println!("{:?}", response);
+        let server = MockServer::start().await;
+        let response = ApiTrackResponse::request_reduced_with(&server.client(), AthleteId::new(29))
+            .await
+            .unwrap();
+        assert_eq!(response.get_track_points().len(), 12);
     }
     #[tokio::test]
     async fn track_replay() {
-        let response = ApiTrackResponse::request_replay(
-            "29",
+        let server = MockServer::start().await;
+        let response = ApiTrackResponse::request_replay_with(
+            &server.client(),
+            AthleteId::new(29),
             Utc.from_utc_datetime(
                 &DateTime::parse_from_rfc3339("2023-06-14T16:50:00Z")
                     .unwrap()
@@ -89,6 +156,49 @@ mod test {
         )
         .await
         .unwrap();
-        println!("{:?}", response);
+        assert_eq!(response.get_track_points().len(), 51);
+    }
+    #[tokio::test]
+    async fn track_lenient() {
+        let server = MockServer::start().await;
+        server.route(
+            "/race/athlete/29/track/latest.pbf",
+            test_util::incomplete_track(),
+        );
+        let client = server.client();
+        assert!(ApiTrackResponse::request_with(&client, AthleteId::new(29))
+            .await
+            .is_err());
+        let (track, report) = ApiTrackResponse::request_lenient_with(&client, AthleteId::new(29))
+            .await
+            .unwrap();
+        assert_eq!(track.points.len(), 60);
+        assert_eq!(track.points[0].altitude_agl, 0.0);
+        assert_eq!(report.points.len(), 1);
+        assert_eq!(report.points[0].repair, Repair::Defaulted);
+        assert_eq!(report.points[0].missing, ["altitude_agl"]);
+
+        let (replay, report) = ApiTrackResponse::request_replay_lenient_with(
+            &client,
+            AthleteId::new(29),
+            Utc.from_utc_datetime(
+                &DateTime::parse_from_rfc3339("2023-06-14T16:50:00Z")
+                    .unwrap()
+                    .naive_utc(),
+            ),
+        )
+        .await
+        .unwrap();
+        assert!(report.is_clean());
+        assert_eq!(replay.points.len(), 51);
+    }
+    #[tokio::test]
+    async fn track_missing() {
+        let server = MockServer::start().await;
+        assert!(
+            ApiTrackResponse::request_with(&server.client(), AthleteId::new(1))
+                .await
+                .is_err()
+        );
     }
 }
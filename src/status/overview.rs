@@ -1,125 +1,210 @@
+use super::{AthleteId, HexColor, TurnPointId};
+use crate::{Client, Error};
 use isocountry::CountryCode;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
+use url::Url;
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 #[serde(rename_all = "camelCase")]
 pub struct Overview {
     #[serde(rename = "3dModels")]
-    three_dimensional_models: ThreeDimensionalModels,
-    athlete_status: Url,
-    athletes: Vec<Athlete>,
-    clock_dates: DateRange,
-    news: News,
-    race_dates: DateRange,
-    turnpoints: Vec<TurnPoint>,
+    pub three_dimensional_models: ThreeDimensionalModels,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub athlete_status: Option<Url>,
+    pub athletes: Vec<Athlete>,
+    pub clock_dates: DateRange,
+    pub news: News,
+    pub race_dates: DateRange,
+    pub turnpoints: Vec<TurnPoint>,
+}
+
+/// A URL of the overview, `None` if missing, empty or not absolute, so one
+/// bad link doesn't fail decoding the whole overview.
+fn lenient_url<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Url>, D::Error> {
+    let url = Option::<String>::deserialize(deserializer)?;
+    Ok(url.and_then(|url| Url::parse(&url).ok()))
 }
 
 impl Overview {
-    pub async fn request() -> Result<Overview, reqwest::Error> {
-        Ok(reqwest::get("https://www.redbullxalps.com/fileadmin/live-tracking/2023/race/feeds/cdn-long/overview.json")
-        .await?
-        .json::<Overview>()
-        .await?)
+    pub async fn request() -> Result<Overview, Error> {
+        Overview::request_with(Client::shared()).await
+    }
+
+    pub async fn request_with(client: &Client) -> Result<Overview, Error> {
+        client.get_json(&Overview::url(client)).await
+    }
+
+    pub(crate) fn url(client: &Client) -> String {
+        client.website_url("fileadmin/live-tracking/2023/race/feeds/cdn-long/overview.json")
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 #[serde(rename_all = "camelCase")]
-struct ThreeDimensionalModels {
-    map_icons: Url,
-    paraglider: Url,
-    status_icons: Url,
+pub struct ThreeDimensionalModels {
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub map_icons: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub paraglider: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub status_icons: Option<Url>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "graphql",
+    derive(async_graphql::SimpleObject),
+    graphql(complex)
+)]
 #[serde(rename_all = "camelCase")]
-struct Athlete {
-    athlete_id: String,
-    bio_url: String,
-    chute_color: HexColor,
-    chute_strings: HexColor,
-    country_code: CountryCode,
-    firstname: String,
-    firstname_short: String,
-    guy_bg_color: HexColor,
-    guy_outlines: HexColor,
-    hide: bool,
-    lastname: String,
-    lt_slug: String,
-    marker_bg_color: HexColor,
-    marker_border_color: HexColor,
-    nationality: String,
-    news: Url,
-    nightpasses_count: usize,
-    portrait: Url,
-    portrait_retina: Url,
-    ranking_portrait: Url,
-    ranking_portrait_retina: Url,
-    status_bg_color: HexColor,
-    team: String,
-    text_color: HexColor,
-    track_color: HexColor,
+pub struct Athlete {
+    pub athlete_id: AthleteId,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub bio_url: Option<Url>,
+    pub chute_color: HexColor,
+    pub chute_strings: HexColor,
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
+    pub country_code: CountryCode,
+    pub firstname: String,
+    pub firstname_short: String,
+    pub guy_bg_color: HexColor,
+    pub guy_outlines: HexColor,
+    pub hide: bool,
+    pub lastname: String,
+    pub lt_slug: String,
+    pub marker_bg_color: HexColor,
+    pub marker_border_color: HexColor,
+    pub nationality: String,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub news: Option<Url>,
+    pub nightpasses_count: usize,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub portrait: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub portrait_retina: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub ranking_portrait: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub ranking_portrait_retina: Option<Url>,
+    pub status_bg_color: HexColor,
+    pub team: String,
+    pub text_color: HexColor,
+    pub track_color: HexColor,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Athlete {
+    /// First and last name, e.g. `Patrick von Känel`.
+    pub fn full_name(&self) -> String {
+        format!("{} {}", self.firstname, self.lastname)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 #[serde(rename_all = "camelCase")]
-struct DateRange {
-    end_time: usize,
-    start_time: usize,
+pub struct DateRange {
+    /// Unix timestamp in seconds.
+    pub end_time: usize,
+    /// Unix timestamp in seconds.
+    pub start_time: usize,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 #[serde(rename_all = "camelCase")]
-struct News {
-    latest: Url,
-    list: Url,
+pub struct News {
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub latest: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub list: Option<Url>,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "graphql",
+    derive(async_graphql::SimpleObject),
+    graphql(complex)
+)]
 #[serde(rename_all = "camelCase")]
-struct TurnPoint {
-    id: String,
-    altitude: usize,
-    altitude_on_mesh: usize,
-    caption: String,
-    country: String,
-    country_code: CountryCode,
-    cylinderradius: usize,
-    dist_to_goal: f64,
-    header: String,
-    hidden: Option<bool>,
-    img: Url,
-    img_retina: Url,
-    lat: f64,
-    leg_dist: f64,
-    lng: f64,
-    lt_slug: String,
+pub struct TurnPoint {
+    pub id: TurnPointId,
+    pub altitude: usize,
+    pub altitude_on_mesh: usize,
+    pub caption: String,
+    pub country: String,
+    #[cfg_attr(feature = "graphql", graphql(skip))]
+    #[cfg_attr(feature = "schema", schemars(with = "String"))]
+    pub country_code: CountryCode,
+    pub cylinderradius: usize,
+    pub dist_to_goal: f64,
+    pub header: String,
+    pub hidden: Option<bool>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub img: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub img_retina: Option<Url>,
+    pub lat: f64,
+    pub leg_dist: f64,
+    pub lng: f64,
+    pub lt_slug: String,
 
-    polygon: Option<String>,
-    sponsor_img: Option<Url>,
-    sponsor_img_retina: Option<Url>,
-    sponsor_url: Option<Url>,
-    sponsors: Option<Vec<Sponsor>>,
-    tot_dist: f64,
-    visible_on_map: bool,
-    weather: Url,
+    pub polygon: Option<String>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub sponsor_img: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub sponsor_img_retina: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub sponsor_url: Option<Url>,
+    pub sponsors: Option<Vec<Sponsor>>,
+    pub tot_dist: f64,
+    pub visible_on_map: bool,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub weather: Option<Url>,
 }
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 #[serde(rename_all = "camelCase")]
-struct Sponsor {
-    sponsor_img: Url,
-    sponsor_img_retina: Url,
-    sponsor_url: Url,
+pub struct Sponsor {
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub sponsor_img: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub sponsor_img_retina: Option<Url>,
+    #[serde(default, deserialize_with = "lenient_url")]
+    pub sponsor_url: Option<Url>,
 }
 
-type HexColor = String;
-type Url = String;
-
 #[cfg(test)]
 mod test {
     use super::Overview;
+    use crate::test_util::{self, MockServer};
+    use serde_json::Value;
     #[tokio::test]
     async fn load_overview() {
-        assert_eq!(true, Overview::request().await.is_ok());
+        let server = MockServer::start().await;
+        assert!(Overview::request_with(&server.client()).await.is_ok());
+    }
+    #[test]
+    fn invalid_urls() {
+        let mut json: Value =
+            serde_json::from_slice(test_util::fixture(test_util::OVERVIEW_PATH).unwrap()).unwrap();
+        json["athletes"][0]["bioUrl"] = "".into();
+        json["athletes"][0]["portrait"] = "/portraits/maurer.jpg".into();
+        json["athletes"][0].as_object_mut().unwrap().remove("news");
+        let overview: Overview = serde_json::from_value(json).unwrap();
+        let athlete = &overview.athletes[0];
+        assert_eq!(athlete.bio_url, None);
+        assert_eq!(athlete.portrait, None);
+        assert_eq!(athlete.news, None);
+        assert!(athlete.portrait_retina.is_some());
     }
 }
@@ -1,69 +1,143 @@
+use super::AthleteId;
+use crate::{Client, Error};
 use chrono::{DateTime, Local};
 use serde::{Deserialize, Serialize};
 // GET https://rbxltdata.redbullxalps.com/race/race-status-replay_{yyyy}-{mm}-{dd} => Vec<RaceStatusReplay>
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
 #[serde(rename_all = "camelCase")]
 pub struct RaceStatusReplay {
-    timestamp: usize,
-    status: Vec<RaceStatus>,
+    pub timestamp: usize,
+    pub status: Vec<RaceStatus>,
 }
 
 impl RaceStatusReplay {
-    pub async fn request(date: DateTime<Local>) -> Result<Vec<RaceStatusReplay>, reqwest::Error> {
-        Ok(reqwest::get(format!(
-            "https://rbxltdata.redbullxalps.com/race/race-status-replay_{}",
+    pub async fn request(date: DateTime<Local>) -> Result<Vec<RaceStatusReplay>, Error> {
+        RaceStatusReplay::request_with(Client::shared(), date).await
+    }
+
+    pub async fn request_with(
+        client: &Client,
+        date: DateTime<Local>,
+    ) -> Result<Vec<RaceStatusReplay>, Error> {
+        client.get_json(&RaceStatusReplay::url(client, date)).await
+    }
+
+    pub(crate) fn url(client: &Client, date: DateTime<Local>) -> String {
+        client.data_url(&format!(
+            "race/race-status-replay_{}",
             date.format("%Y-%m-%d")
         ))
-        .await?
-        .json::<Vec<RaceStatusReplay>>()
-        .await?)
     }
 
-    pub async fn request_today() -> Result<Vec<RaceStatusReplay>, reqwest::Error> {
-        RaceStatusReplay::request(chrono::offset::Local::now()).await
+    pub async fn request_today() -> Result<Vec<RaceStatusReplay>, Error> {
+        RaceStatusReplay::request_today_with(Client::shared()).await
+    }
+
+    pub async fn request_today_with(client: &Client) -> Result<Vec<RaceStatusReplay>, Error> {
+        RaceStatusReplay::request_with(client, chrono::offset::Local::now()).await
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(
+    feature = "graphql",
+    derive(async_graphql::SimpleObject),
+    graphql(complex)
+)]
 #[serde(rename_all = "camelCase")]
 pub struct RaceStatus {
-    athlete_id: String,
-    timestamp: usize,
-    status: AthleteStatus,
-    distance_to_goal: f64,
-    altitude: usize,
+    pub athlete_id: AthleteId,
+    /// Unix timestamp in seconds.
+    pub timestamp: usize,
+    pub status: AthleteStatus,
+    /// Kilometres.
+    pub distance_to_goal: f64,
+    /// Metres above sea level.
+    pub altitude: usize,
 }
 
 impl RaceStatus {
-    pub async fn request() -> Result<Vec<RaceStatus>, reqwest::Error> {
-        Ok(reqwest::get(format!(
-            "https://rbxltdata.redbullxalps.com/race/race-status"
-        ))
-        .await?
-        .json::<Vec<RaceStatus>>()
-        .await?)
+    pub async fn request() -> Result<Vec<RaceStatus>, Error> {
+        RaceStatus::request_with(Client::shared()).await
+    }
+
+    pub async fn request_with(client: &Client) -> Result<Vec<RaceStatus>, Error> {
+        client.get_json(&RaceStatus::url(client)).await
+    }
+
+    pub(crate) fn url(client: &Client) -> String {
+        client.data_url("race/race-status")
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "graphql", derive(async_graphql::Enum))]
 #[serde(rename_all = "UPPERCASE")]
-enum AthleteStatus {
+pub enum AthleteStatus {
     Rest,
     Fly,
     Hike,
 }
 
+impl AthleteStatus {
+    /// Name as used in the feeds.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AthleteStatus::Rest => "REST",
+            AthleteStatus::Fly => "FLY",
+            AthleteStatus::Hike => "HIKE",
+        }
+    }
+
+    pub fn parse(status: &str) -> Option<AthleteStatus> {
+        match status {
+            "REST" => Some(AthleteStatus::Rest),
+            "FLY" => Some(AthleteStatus::Fly),
+            "HIKE" => Some(AthleteStatus::Hike),
+            _ => None,
+        }
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::{RaceStatus, RaceStatusReplay};
+    use crate::test_util::{self, MockServer};
 
     #[tokio::test]
     async fn replay_today() {
-        assert_eq!(true, RaceStatusReplay::request_today().await.is_ok())
+        let server = MockServer::start().await;
+        server.route(
+            &format!(
+                "/race/race-status-replay_{}",
+                chrono::offset::Local::now().format("%Y-%m-%d")
+            ),
+            test_util::fixture(&format!(
+                "/race/race-status-replay_{}",
+                test_util::REPLAY_DATE
+            ))
+            .unwrap(),
+        );
+        assert!(RaceStatusReplay::request_today_with(&server.client())
+            .await
+            .is_ok())
     }
 
     #[tokio::test]
     async fn status() {
-        assert_eq!(true, RaceStatus::request().await.is_ok())
+        let server = MockServer::start().await;
+        assert!(RaceStatus::request_with(&server.client()).await.is_ok())
+    }
+
+    #[tokio::test]
+    async fn status_retries_server_errors() {
+        let server = MockServer::start().await;
+        server.fail("/race/race-status", 503, 2);
+        assert!(RaceStatus::request_with(&server.client()).await.is_ok());
+        assert_eq!(server.hits("/race/race-status"), 3);
     }
 }
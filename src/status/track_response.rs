@@ -695,16 +695,17 @@ impl ::protobuf::reflect::ProtobufValue for ApiTrackPoint {
 }
 
 static file_descriptor_proto_data: &'static [u8] = b"\
-    \n\x1fsrc/status/track_response.proto\x12\x0etrack_response\"s\n\x10ApiT\
-    rackResponse\x12\x1d\n\nathlete_id\x18\x01\x20\x02(\x05R\tathleteId\x12@\
-    \n\x0ctrack_points\x18\x02\x20\x03(\x0b2\x1d.track_response.ApiTrackPoin\
-    tR\x0btrackPoints\"\xe5\x01\n\rApiTrackPoint\x12\x1c\n\ttimestamp\x18\
-    \x01\x20\x02(\x01R\ttimestamp\x12\x10\n\x03lat\x18\x02\x20\x02(\x02R\x03\
-    lat\x12\x10\n\x03lng\x18\x03\x20\x02(\x02R\x03lng\x12\x1a\n\x08altitude\
-    \x18\x04\x20\x02(\x02R\x08altitude\x12!\n\x0caltitude_agl\x18\x05\x20\
-    \x02(\x02R\x0baltitudeAgl\x12\x16\n\x06status\x18\x06\x20\x01(\tR\x06sta\
-    tus\x12\x14\n\x05speed\x18\x07\x20\x02(\x02R\x05speed\x12%\n\x0evertical\
-    _speed\x18\x08\x20\x02(\x02R\rverticalSpeed\
+    \n\x1fsrc/status/track_response.proto\x12\x0etrack_response\"y\n\x10ApiT\
+    rackResponse\x12\x1f\n\nathlete_id\x18\x01\x20\x02(\x05R\tathleteIdB\0\
+    \x12B\n\x0ctrack_points\x18\x02\x20\x03(\x0b2\x1d.track_response.ApiTrac\
+    kPointR\x0btrackPointsB\0:\0\"\xf7\x01\n\rApiTrackPoint\x12\x1e\n\ttimes\
+    tamp\x18\x01\x20\x02(\x01R\ttimestampB\0\x12\x12\n\x03lat\x18\x02\x20\
+    \x02(\x02R\x03latB\0\x12\x12\n\x03lng\x18\x03\x20\x02(\x02R\x03lngB\0\
+    \x12\x1c\n\x08altitude\x18\x04\x20\x02(\x02R\x08altitudeB\0\x12#\n\x0cal\
+    titude_agl\x18\x05\x20\x02(\x02R\x0baltitudeAglB\0\x12\x18\n\x06status\
+    \x18\x06\x20\x01(\tR\x06statusB\0\x12\x16\n\x05speed\x18\x07\x20\x02(\
+    \x02R\x05speedB\0\x12'\n\x0evertical_speed\x18\x08\x20\x02(\x02R\rvertic\
+    alSpeedB\0:\0B\0b\x06proto2\
 ";
 
 static file_descriptor_proto_lazy: ::protobuf::rt::LazyV2<::protobuf::descriptor::FileDescriptorProto> = ::protobuf::rt::LazyV2::INIT;
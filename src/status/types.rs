@@ -0,0 +1,332 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use std::{fmt, str::FromStr};
+
+/// Id of an athlete, a string in the JSON feeds and an `int32` in the
+/// protobuf track feed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AthleteId(i32);
+
+impl AthleteId {
+    pub const fn new(id: i32) -> AthleteId {
+        AthleteId(id)
+    }
+
+    pub const fn get(self) -> i32 {
+        self.0
+    }
+}
+
+impl From<i32> for AthleteId {
+    fn from(id: i32) -> Self {
+        AthleteId(id)
+    }
+}
+
+impl From<AthleteId> for i32 {
+    fn from(id: AthleteId) -> Self {
+        id.0
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseAthleteIdError(String);
+
+impl fmt::Display for ParseAthleteIdError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid athlete id {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseAthleteIdError {}
+
+impl FromStr for AthleteId {
+    type Err = ParseAthleteIdError;
+
+    /// Parses the decimal digits of a non-negative id.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseAthleteIdError(s.to_string());
+        // `i32::from_str` would also accept a sign, e.g. `+29` or `-29`.
+        if s.is_empty() || !s.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(invalid());
+        }
+        s.parse().map(AthleteId).map_err(|_| invalid())
+    }
+}
+
+impl fmt::Display for AthleteId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl Serialize for AthleteId {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AthleteId {
+    /// Takes the feeds' strings, and numbers as written by older versions.
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct Visitor;
+
+        impl de::Visitor<'_> for Visitor {
+            type Value = AthleteId;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an athlete id")
+            }
+
+            fn visit_str<E: de::Error>(self, v: &str) -> Result<AthleteId, E> {
+                v.parse().map_err(E::custom)
+            }
+
+            fn visit_i64<E: de::Error>(self, v: i64) -> Result<AthleteId, E> {
+                u64::try_from(v)
+                    .map_err(|_| E::custom(ParseAthleteIdError(v.to_string())))
+                    .and_then(|v| self.visit_u64(v))
+            }
+
+            fn visit_u64<E: de::Error>(self, v: u64) -> Result<AthleteId, E> {
+                i32::try_from(v).map(AthleteId).map_err(E::custom)
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+/// Id of a turnpoint, e.g. `tp1`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[serde(transparent)]
+pub struct TurnPointId(String);
+
+impl TurnPointId {
+    pub fn new(id: impl Into<String>) -> TurnPointId {
+        TurnPointId(id.into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl fmt::Display for TurnPointId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+/// An sRGB colour written as `#RRGGBB`, or `#RRGGBBAA` if not opaque.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HexColor {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    /// Opacity, 255 for opaque.
+    pub a: u8,
+}
+
+impl HexColor {
+    pub const fn rgb(r: u8, g: u8, b: u8) -> HexColor {
+        HexColor { r, g, b, a: 255 }
+    }
+
+    pub const fn rgba(r: u8, g: u8, b: u8, a: u8) -> HexColor {
+        HexColor { r, g, b, a }
+    }
+
+    pub fn to_rgb(self) -> [u8; 3] {
+        [self.r, self.g, self.b]
+    }
+
+    pub fn to_rgba(self) -> [u8; 4] {
+        [self.r, self.g, self.b, self.a]
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseColorError(String);
+
+impl fmt::Display for ParseColorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid hex color {:?}", self.0)
+    }
+}
+
+impl std::error::Error for ParseColorError {}
+
+impl FromStr for HexColor {
+    type Err = ParseColorError;
+
+    /// Parses `#RGB`, `#RGBA`, `#RRGGBB` or `#RRGGBBAA`, with or without the `#`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ParseColorError(s.to_string());
+        let hex = s.strip_prefix('#').unwrap_or(s);
+        // `from_str_radix` would also accept a sign, e.g. `+f`.
+        if !hex.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+            return Err(invalid());
+        }
+        let digit = |i: usize, len: usize| {
+            u8::from_str_radix(&hex[i * len..(i + 1) * len], 16)
+                .map(|value| if len == 1 { value * 17 } else { value })
+                .map_err(|_| invalid())
+        };
+        let len = match hex.len() {
+            3 | 4 => 1,
+            6 | 8 => 2,
+            _ => return Err(invalid()),
+        };
+        let a = if hex.len() / len == 4 {
+            digit(3, len)?
+        } else {
+            255
+        };
+        Ok(HexColor::rgba(
+            digit(0, len)?,
+            digit(1, len)?,
+            digit(2, len)?,
+            a,
+        ))
+    }
+}
+
+impl fmt::Display for HexColor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "#{:02X}{:02X}{:02X}", self.r, self.g, self.b)?;
+        if self.a != 255 {
+            write!(f, "{:02X}", self.a)?;
+        }
+        Ok(())
+    }
+}
+
+impl Serialize for HexColor {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for HexColor {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(de::Error::custom)
+    }
+}
+
+#[cfg(feature = "schema")]
+mod schema {
+    use super::{AthleteId, HexColor, TurnPointId};
+    use schemars::{gen::SchemaGenerator, schema::Schema, JsonSchema};
+
+    macro_rules! string_schema {
+        ($($t:ty),*) => {$(
+            impl JsonSchema for $t {
+                fn schema_name() -> String {
+                    stringify!($t).to_string()
+                }
+
+                fn json_schema(gen: &mut SchemaGenerator) -> Schema {
+                    String::json_schema(gen)
+                }
+            }
+        )*};
+    }
+
+    string_schema!(AthleteId, TurnPointId, HexColor);
+}
+
+#[cfg(feature = "graphql")]
+mod graphql {
+    use super::{AthleteId, HexColor, TurnPointId};
+    use async_graphql::{InputValueError, InputValueResult, Scalar, ScalarType, Value};
+
+    /// Id of an athlete, taken as a string or an int.
+    #[Scalar]
+    impl ScalarType for AthleteId {
+        fn parse(value: Value) -> InputValueResult<Self> {
+            match &value {
+                Value::String(s) => Ok(s.parse()?),
+                Value::Number(n) => n
+                    .as_u64()
+                    .and_then(|n| i32::try_from(n).ok())
+                    .map(AthleteId)
+                    .ok_or_else(|| InputValueError::expected_type(value)),
+                _ => Err(InputValueError::expected_type(value)),
+            }
+        }
+
+        fn to_value(&self) -> Value {
+            Value::String(self.to_string())
+        }
+    }
+
+    #[Scalar]
+    impl ScalarType for TurnPointId {
+        fn parse(value: Value) -> InputValueResult<Self> {
+            match value {
+                Value::String(s) => Ok(TurnPointId(s)),
+                value => Err(InputValueError::expected_type(value)),
+            }
+        }
+
+        fn to_value(&self) -> Value {
+            Value::String(self.0.clone())
+        }
+    }
+
+    /// `#RRGGBB`, or `#RRGGBBAA` if not opaque.
+    #[Scalar]
+    impl ScalarType for HexColor {
+        fn parse(value: Value) -> InputValueResult<Self> {
+            match &value {
+                Value::String(s) => Ok(s.parse()?),
+                _ => Err(InputValueError::expected_type(value)),
+            }
+        }
+
+        fn to_value(&self) -> Value {
+            Value::String(self.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{AthleteId, HexColor};
+
+    #[test]
+    fn athlete_id() {
+        let id: AthleteId = serde_json::from_str("\"29\"").unwrap();
+        assert_eq!(id, AthleteId::new(29));
+        assert_eq!(serde_json::from_str::<AthleteId>("29").unwrap(), id);
+        assert_eq!(serde_json::to_string(&id).unwrap(), "\"29\"");
+        assert_eq!(i32::from(id), 29);
+        assert!(serde_json::from_str::<AthleteId>("\"tp1\"").is_err());
+        for invalid in ["\"+29\"", "\"-29\"", "\"\"", "-29", "2147483648"] {
+            assert!(
+                serde_json::from_str::<AthleteId>(invalid).is_err(),
+                "{}",
+                invalid
+            );
+        }
+        assert!(" 29".parse::<AthleteId>().is_err());
+    }
+
+    #[test]
+    fn hex_color() {
+        let color: HexColor = "#0050A0".parse().unwrap();
+        assert_eq!(color.to_rgba(), [0x00, 0x50, 0xa0, 0xff]);
+        assert_eq!(color.to_string(), "#0050A0");
+        assert_eq!("fff".parse(), Ok(HexColor::rgb(255, 255, 255)));
+        let translucent: HexColor = "#1f1f1f80".parse().unwrap();
+        assert_eq!(translucent.to_rgba(), [0x1f, 0x1f, 0x1f, 0x80]);
+        assert_eq!(
+            serde_json::to_string(&translucent).unwrap(),
+            "\"#1F1F1F80\""
+        );
+        for invalid in ["", "#12345", "#GGGGGG", "#ääää", "#+f+f+f"] {
+            assert!(invalid.parse::<HexColor>().is_err(), "{}", invalid);
+        }
+    }
+}
@@ -0,0 +1,499 @@
+//! Local SQLite archive of the feeds, so the race stays queryable after the
+//! live endpoints are gone.
+//!
+//! Race status rows are keyed by athlete and timestamp, track points by
+//! athlete and point timestamp; storing the same data again only updates it.
+//! Replayed snapshots keep which status of every athlete they contained.
+
+use crate::{
+    AthleteId, AthleteStatus, Error, Overview, RaceStatus, RaceStatusReplay, Track, TrackPoint,
+};
+use chrono::{DateTime, Utc};
+use rusqlite::{
+    params,
+    types::{FromSql, FromSqlError, FromSqlResult, ToSqlOutput, ValueRef},
+    Connection, OptionalExtension, Row, ToSql,
+};
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS overview (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    fetched_at INTEGER NOT NULL,
+    json TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS race_status (
+    athlete_id INTEGER NOT NULL,
+    timestamp INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    distance_to_goal REAL NOT NULL,
+    altitude INTEGER NOT NULL,
+    PRIMARY KEY (athlete_id, timestamp)
+);
+CREATE TABLE IF NOT EXISTS track_point (
+    athlete_id INTEGER NOT NULL,
+    timestamp REAL NOT NULL,
+    lat REAL NOT NULL,
+    lng REAL NOT NULL,
+    altitude REAL NOT NULL,
+    altitude_agl REAL NOT NULL,
+    status TEXT,
+    speed REAL NOT NULL,
+    vertical_speed REAL NOT NULL,
+    PRIMARY KEY (athlete_id, timestamp)
+);
+CREATE TABLE IF NOT EXISTS replay_snapshot (
+    timestamp INTEGER NOT NULL,
+    position INTEGER NOT NULL,
+    athlete_id INTEGER NOT NULL,
+    status_timestamp INTEGER NOT NULL,
+    PRIMARY KEY (timestamp, position)
+);
+CREATE INDEX IF NOT EXISTS race_status_timestamp ON race_status (timestamp);
+";
+
+/// Databases created before athlete ids were integers stored them as `TEXT`
+/// in `race_status`, run before [`SCHEMA`] recreates the table.
+const MIGRATE_TEXT_ATHLETE_IDS: &str = "
+ALTER TABLE race_status RENAME TO race_status_text_ids;
+DROP INDEX race_status_timestamp;
+";
+
+const COPY_TEXT_ATHLETE_IDS: &str = "
+INSERT INTO race_status (athlete_id, timestamp, status, distance_to_goal, altitude)
+SELECT CAST(athlete_id AS INTEGER), timestamp, status, distance_to_goal, altitude
+FROM race_status_text_ids;
+DROP TABLE race_status_text_ids;
+";
+
+pub struct Storage {
+    conn: Connection,
+}
+
+impl Storage {
+    /// Opens or creates the database at `path`.
+    pub fn open(path: impl AsRef<Path>) -> Result<Storage, Error> {
+        Storage::init(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Storage, Error> {
+        Storage::init(Connection::open_in_memory()?)
+    }
+
+    fn init(conn: Connection) -> Result<Storage, Error> {
+        let athlete_id_type: Option<String> = conn
+            .query_row(
+                "SELECT type FROM pragma_table_info('race_status') WHERE name = 'athlete_id'",
+                [],
+                |row| row.get(0),
+            )
+            .optional()?;
+        let tx = conn.unchecked_transaction()?;
+        if athlete_id_type.as_deref() == Some("TEXT") {
+            tx.execute_batch(MIGRATE_TEXT_ATHLETE_IDS)?;
+            tx.execute_batch(SCHEMA)?;
+            tx.execute_batch(COPY_TEXT_ATHLETE_IDS)?;
+        } else {
+            tx.execute_batch(SCHEMA)?;
+        }
+        tx.commit()?;
+        Ok(Storage { conn })
+    }
+
+    /// Replaces the stored overview.
+    pub fn store_overview(
+        &self,
+        overview: &Overview,
+        fetched_at: DateTime<Utc>,
+    ) -> Result<(), Error> {
+        self.conn.execute(
+            "INSERT INTO overview (id, fetched_at, json) VALUES (1, ?1, ?2)
+             ON CONFLICT (id) DO UPDATE SET fetched_at = excluded.fetched_at, json = excluded.json",
+            params![fetched_at.timestamp(), serde_json::to_string(overview)?],
+        )?;
+        Ok(())
+    }
+
+    pub fn overview(&self) -> Result<Option<Overview>, Error> {
+        let json: Option<String> = self
+            .conn
+            .query_row("SELECT json FROM overview WHERE id = 1", [], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        Ok(json.map(|json| serde_json::from_str(&json)).transpose()?)
+    }
+
+    pub fn store_race_status(&self, status: &[RaceStatus]) -> Result<(), Error> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO race_status (athlete_id, timestamp, status, distance_to_goal, altitude)
+                 VALUES (?1, ?2, ?3, ?4, ?5)
+                 ON CONFLICT (athlete_id, timestamp) DO UPDATE SET
+                    status = excluded.status,
+                    distance_to_goal = excluded.distance_to_goal,
+                    altitude = excluded.altitude",
+            )?;
+            for status in status {
+                insert.execute(params![
+                    status.athlete_id,
+                    status.timestamp as i64,
+                    status.status,
+                    status.distance_to_goal,
+                    status.altitude as i64,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Stores every status of every snapshot of a replayed day, and the snapshots.
+    pub fn store_replay(&self, replay: &[RaceStatusReplay]) -> Result<(), Error> {
+        let status: Vec<RaceStatus> = replay
+            .iter()
+            .flat_map(|snapshot| snapshot.status.iter().cloned())
+            .collect();
+        self.store_race_status(&status)?;
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO replay_snapshot (timestamp, position, athlete_id, status_timestamp)
+                 VALUES (?1, ?2, ?3, ?4)
+                 ON CONFLICT (timestamp, position) DO UPDATE SET
+                    athlete_id = excluded.athlete_id,
+                    status_timestamp = excluded.status_timestamp",
+            )?;
+            for snapshot in replay {
+                for (position, status) in snapshot.status.iter().enumerate() {
+                    insert.execute(params![
+                        snapshot.timestamp as i64,
+                        position as i64,
+                        status.athlete_id,
+                        status.timestamp as i64,
+                    ])?;
+                }
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// The last stored replay snapshot taken at or before `at`.
+    pub fn snapshot_at(&self, at: DateTime<Utc>) -> Result<Option<RaceStatusReplay>, Error> {
+        let timestamp: Option<i64> = self.conn.query_row(
+            "SELECT MAX(timestamp) FROM replay_snapshot WHERE timestamp <= ?1",
+            params![at.timestamp()],
+            |row| row.get(0),
+        )?;
+        let Some(timestamp) = timestamp else {
+            return Ok(None);
+        };
+        let mut select = self.conn.prepare_cached(
+            "SELECT s.athlete_id, s.timestamp, s.status, s.distance_to_goal, s.altitude
+             FROM replay_snapshot r
+             JOIN race_status s
+                ON s.athlete_id = r.athlete_id AND s.timestamp = r.status_timestamp
+             WHERE r.timestamp = ?1
+             ORDER BY r.position",
+        )?;
+        let status = select
+            .query_map(params![timestamp], race_status)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Some(RaceStatusReplay {
+            timestamp: timestamp as usize,
+            status,
+        }))
+    }
+
+    pub fn store_track(&self, track: &Track) -> Result<(), Error> {
+        let tx = self.conn.unchecked_transaction()?;
+        {
+            let mut insert = tx.prepare_cached(
+                "INSERT INTO track_point (athlete_id, timestamp, lat, lng, altitude, altitude_agl, status, speed, vertical_speed)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)
+                 ON CONFLICT (athlete_id, timestamp) DO UPDATE SET
+                    lat = excluded.lat,
+                    lng = excluded.lng,
+                    altitude = excluded.altitude,
+                    altitude_agl = excluded.altitude_agl,
+                    status = excluded.status,
+                    speed = excluded.speed,
+                    vertical_speed = excluded.vertical_speed",
+            )?;
+            for point in &track.points {
+                insert.execute(params![
+                    track.athlete_id,
+                    point.timestamp,
+                    point.lat,
+                    point.lng,
+                    point.altitude,
+                    point.altitude_agl,
+                    point.status,
+                    point.speed,
+                    point.vertical_speed,
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Track points of the athlete between `from` and `to` (inclusive), in order.
+    pub fn track(
+        &self,
+        athlete_id: AthleteId,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> Result<Track, Error> {
+        let mut select = self.conn.prepare_cached(
+            "SELECT timestamp, lat, lng, altitude, altitude_agl, status, speed, vertical_speed
+             FROM track_point
+             WHERE athlete_id = ?1 AND timestamp >= ?2 AND timestamp <= ?3
+             ORDER BY timestamp",
+        )?;
+        let points = select
+            .query_map(params![athlete_id, seconds(from), seconds(to)], track_point)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(Track { athlete_id, points })
+    }
+
+    /// Timestamp of the newest stored track point of the athlete.
+    pub fn latest_track_timestamp(&self, athlete_id: AthleteId) -> Result<Option<f64>, Error> {
+        Ok(self.conn.query_row(
+            "SELECT MAX(timestamp) FROM track_point WHERE athlete_id = ?1",
+            params![athlete_id],
+            |row| row.get(0),
+        )?)
+    }
+
+    /// Timestamp of the newest stored race status of any athlete.
+    pub fn latest_status_timestamp(&self) -> Result<Option<i64>, Error> {
+        Ok(self
+            .conn
+            .query_row("SELECT MAX(timestamp) FROM race_status", [], |row| {
+                row.get(0)
+            })?)
+    }
+
+    /// Last known status of the athlete at `at`.
+    pub fn status_at(
+        &self,
+        athlete_id: AthleteId,
+        at: DateTime<Utc>,
+    ) -> Result<Option<RaceStatus>, Error> {
+        Ok(self
+            .conn
+            .query_row(
+                "SELECT athlete_id, timestamp, status, distance_to_goal, altitude
+                 FROM race_status
+                 WHERE athlete_id = ?1 AND timestamp <= ?2
+                 ORDER BY timestamp DESC LIMIT 1",
+                params![athlete_id, at.timestamp()],
+                race_status,
+            )
+            .optional()?)
+    }
+
+    /// Last known status of every athlete at `at`.
+    pub fn race_status_at(&self, at: DateTime<Utc>) -> Result<Vec<RaceStatus>, Error> {
+        let mut select = self.conn.prepare_cached(
+            "SELECT athlete_id, MAX(timestamp), status, distance_to_goal, altitude
+             FROM race_status
+             WHERE timestamp <= ?1
+             GROUP BY athlete_id
+             ORDER BY distance_to_goal",
+        )?;
+        let status = select
+            .query_map(params![at.timestamp()], race_status)?
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(status)
+    }
+}
+
+fn seconds(time: DateTime<Utc>) -> f64 {
+    time.timestamp_millis() as f64 / 1000.0
+}
+
+fn race_status(row: &Row) -> rusqlite::Result<RaceStatus> {
+    Ok(RaceStatus {
+        athlete_id: row.get(0)?,
+        timestamp: row.get::<_, i64>(1)? as usize,
+        status: row.get(2)?,
+        distance_to_goal: row.get(3)?,
+        altitude: row.get::<_, i64>(4)? as usize,
+    })
+}
+
+fn track_point(row: &Row) -> rusqlite::Result<TrackPoint> {
+    Ok(TrackPoint {
+        timestamp: row.get(0)?,
+        lat: row.get(1)?,
+        lng: row.get(2)?,
+        altitude: row.get(3)?,
+        altitude_agl: row.get(4)?,
+        status: row.get(5)?,
+        speed: row.get(6)?,
+        vertical_speed: row.get(7)?,
+    })
+}
+
+impl ToSql for AthleteStatus {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.as_str().into())
+    }
+}
+
+impl FromSql for AthleteStatus {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let status = value.as_str()?;
+        AthleteStatus::parse(status)
+            .ok_or_else(|| FromSqlError::Other(format!("unknown athlete status {}", status).into()))
+    }
+}
+
+impl ToSql for AthleteId {
+    fn to_sql(&self) -> rusqlite::Result<ToSqlOutput<'_>> {
+        Ok(self.get().into())
+    }
+}
+
+impl FromSql for AthleteId {
+    fn column_result(value: ValueRef<'_>) -> FromSqlResult<Self> {
+        let id = value.as_i64()?;
+        i32::try_from(id)
+            .map(AthleteId::new)
+            .map_err(|_| FromSqlError::OutOfRange(id))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::Storage;
+    use crate::{
+        test_util, ApiTrackResponse, AthleteId, Overview, RaceStatus, RaceStatusReplay, Track,
+    };
+    use chrono::{DateTime, Utc};
+
+    fn time(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().into()
+    }
+
+    fn track() -> Track {
+        let response: ApiTrackResponse = protobuf::Message::parse_from_bytes(
+            test_util::fixture("/race/athlete/29/track/latest.pbf").unwrap(),
+        )
+        .unwrap();
+        Track::from(response)
+    }
+
+    #[test]
+    fn overview_round_trip() {
+        let storage = Storage::open_in_memory().unwrap();
+        let overview: Overview =
+            serde_json::from_slice(test_util::fixture(test_util::OVERVIEW_PATH).unwrap()).unwrap();
+        storage.store_overview(&overview, Utc::now()).unwrap();
+        storage.store_overview(&overview, Utc::now()).unwrap();
+        assert_eq!(
+            format!("{:?}", storage.overview().unwrap().unwrap()),
+            format!("{:?}", overview)
+        );
+    }
+
+    #[test]
+    fn track_in_range_is_idempotent() {
+        let storage = Storage::open_in_memory().unwrap();
+        storage.store_track(&track()).unwrap();
+        storage.store_track(&track()).unwrap();
+        let all = storage
+            .track(
+                AthleteId::new(29),
+                time("2023-06-14T00:00:00Z"),
+                time("2023-06-15T00:00:00Z"),
+            )
+            .unwrap();
+        assert_eq!(all, track());
+        let range = storage
+            .track(
+                AthleteId::new(29),
+                time("2023-06-14T16:10:00Z"),
+                time("2023-06-14T16:19:00Z"),
+            )
+            .unwrap();
+        assert_eq!(range.points.len(), 10);
+        assert_eq!(range.points[0], track().points[10]);
+    }
+
+    #[test]
+    fn status_at_time() {
+        let storage = Storage::open_in_memory().unwrap();
+        let replay: Vec<RaceStatusReplay> = serde_json::from_slice(
+            test_util::fixture("/race/race-status-replay_2023-06-14").unwrap(),
+        )
+        .unwrap();
+        let status: Vec<RaceStatus> =
+            serde_json::from_slice(test_util::fixture("/race/race-status").unwrap()).unwrap();
+        storage.store_replay(&replay).unwrap();
+        storage.store_race_status(&status).unwrap();
+
+        assert_eq!(
+            storage
+                .status_at(AthleteId::new(29), time("2023-06-14T11:00:00Z"))
+                .unwrap(),
+            Some(replay[1].status[0].clone())
+        );
+        assert_eq!(
+            storage
+                .status_at(AthleteId::new(29), time("2023-06-14T05:00:00Z"))
+                .unwrap(),
+            None
+        );
+        let latest = storage
+            .race_status_at(time("2023-06-15T00:00:00Z"))
+            .unwrap();
+        assert_eq!(latest, status);
+
+        assert_eq!(
+            storage.snapshot_at(time("2023-06-14T11:00:00Z")).unwrap(),
+            Some(replay[1].clone())
+        );
+        assert_eq!(
+            storage.snapshot_at(time("2023-06-14T05:00:00Z")).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn migrates_text_athlete_ids() {
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE race_status (
+                athlete_id TEXT NOT NULL,
+                timestamp INTEGER NOT NULL,
+                status TEXT NOT NULL,
+                distance_to_goal REAL NOT NULL,
+                altitude INTEGER NOT NULL,
+                PRIMARY KEY (athlete_id, timestamp)
+            );
+            CREATE INDEX race_status_timestamp ON race_status (timestamp);
+            INSERT INTO race_status VALUES ('29', 1686736800, 'FLY', 1000.5, 2000);",
+        )
+        .unwrap();
+        let storage = Storage::init(conn).unwrap();
+        let status = storage
+            .status_at(AthleteId::new(29), time("2023-06-15T00:00:00Z"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(status.distance_to_goal, 1000.5);
+        let ids: i64 = storage
+            .conn
+            .query_row(
+                "SELECT COUNT(*) FROM race_status WHERE typeof(athlete_id) = 'integer'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert_eq!(ids, 1);
+    }
+}
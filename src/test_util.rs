@@ -0,0 +1,232 @@
+//! Local stand-in for the Red Bull X-Alps feeds, for tests that must not depend
+//! on the live servers.
+//!
+//! [`MockServer::start`] serves the recorded fixtures in `fixtures/` under the
+//! same paths as the real hosts, so a [`Client`] built with
+//! [`MockServer::client`] works unchanged against it.
+
+use crate::{ApiTrackResponse, AthleteId, Client, ClientBuilder, RequestPolicy};
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use protobuf::{CodedOutputStream, Message};
+use std::{
+    collections::HashMap,
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::oneshot;
+
+pub const OVERVIEW_PATH: &str = "/fileadmin/live-tracking/2023/race/feeds/cdn-long/overview.json";
+
+/// Date of the recorded `race-status-replay_*` fixture.
+pub const REPLAY_DATE: &str = "2023-06-14";
+
+/// Athlete with recorded `.pbf` tracks.
+pub const TRACK_ATHLETE_ID: AthleteId = AthleteId::new(29);
+
+/// Time of the recorded `latest-replay` track.
+pub const TRACK_REPLAY_TIME: &str = "2023-06-14T16:50:00Z";
+
+const FIXTURES: &[(&str, &[u8])] = &[
+    (
+        OVERVIEW_PATH,
+        include_bytes!(
+            "../fixtures/fileadmin/live-tracking/2023/race/feeds/cdn-long/overview.json"
+        ),
+    ),
+    (
+        "/race/race-status",
+        include_bytes!("../fixtures/race/race-status"),
+    ),
+    (
+        "/race/race-status-replay_2023-06-14",
+        include_bytes!("../fixtures/race/race-status-replay_2023-06-14"),
+    ),
+    (
+        "/race/athlete/29/track/latest.pbf",
+        include_bytes!("../fixtures/race/athlete/29/track/latest.pbf"),
+    ),
+    (
+        "/race/athlete/29/track/reduced.pbf",
+        include_bytes!("../fixtures/race/athlete/29/track/reduced.pbf"),
+    ),
+    (
+        "/race/athlete/29/track/latest-replay/2023-06-14T16:50:00Z.pbf",
+        include_bytes!("../fixtures/race/athlete/29/track/latest-replay/2023-06-14T16-50-00Z.pbf"),
+    ),
+];
+
+/// Body of the bundled fixture served at `path`.
+pub fn fixture(path: &str) -> Option<&'static [u8]> {
+    FIXTURES
+        .iter()
+        .find(|(fixture_path, _)| *fixture_path == path)
+        .map(|(_, body)| *body)
+}
+
+/// The recorded `latest.pbf` track with the `altitude_agl` of its first point
+/// removed, which only [`crate::Track::decode_lenient`] accepts.
+pub fn incomplete_track() -> Vec<u8> {
+    let latest = fixture("/race/athlete/29/track/latest.pbf").expect("missing track fixture");
+    let mut response = ApiTrackResponse::decode(latest).expect("invalid track fixture");
+    response.mut_track_points()[0].clear_altitude_agl();
+    // `write_to_bytes` would refuse the missing required field.
+    response.compute_size();
+    let mut bytes = Vec::new();
+    let mut output = CodedOutputStream::vec(&mut bytes);
+    response
+        .write_to_with_cached_sizes(&mut output)
+        .expect("failed to encode the track");
+    output.flush().expect("failed to encode the track");
+    drop(output);
+    bytes
+}
+
+#[derive(Default)]
+struct State {
+    routes: HashMap<String, Vec<u8>>,
+    failures: HashMap<String, (StatusCode, usize)>,
+    hits: HashMap<String, usize>,
+}
+
+impl State {
+    fn respond(&mut self, path: &str) -> Response<Body> {
+        *self.hits.entry(path.to_string()).or_default() += 1;
+        if let Some((status, remaining)) = self.failures.get_mut(path) {
+            if *remaining > 0 {
+                *remaining -= 1;
+                return Self::status(*status);
+            }
+        }
+        match self.routes.get(path) {
+            Some(body) => Response::builder()
+                .header(
+                    "content-type",
+                    if path.ends_with(".pbf") {
+                        "application/x-protobuf"
+                    } else {
+                        "application/json"
+                    },
+                )
+                .body(Body::from(body.clone()))
+                .unwrap(),
+            None => Self::status(StatusCode::NOT_FOUND),
+        }
+    }
+
+    fn status(status: StatusCode) -> Response<Body> {
+        Response::builder()
+            .status(status)
+            .body(Body::empty())
+            .unwrap()
+    }
+}
+
+/// HTTP server on a random local port, shut down when dropped.
+pub struct MockServer {
+    addr: SocketAddr,
+    state: Arc<Mutex<State>>,
+    shutdown: Option<oneshot::Sender<()>>,
+}
+
+impl MockServer {
+    /// Starts a server that serves the bundled fixtures.
+    pub async fn start() -> MockServer {
+        let server = MockServer::empty().await;
+        for (path, body) in FIXTURES {
+            server.route(path, *body);
+        }
+        server
+    }
+
+    /// Starts a server without any routes.
+    pub async fn empty() -> MockServer {
+        let state = Arc::new(Mutex::new(State::default()));
+        let service_state = state.clone();
+        let make_service = make_service_fn(move |_| {
+            let state = service_state.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let response = state.lock().unwrap().respond(request.uri().path());
+                    async move { Ok::<_, Infallible>(response) }
+                }))
+            }
+        });
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let addr = server.local_addr();
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        tokio::spawn(server.with_graceful_shutdown(async {
+            shutdown_rx.await.ok();
+        }));
+        MockServer {
+            addr,
+            state,
+            shutdown: Some(shutdown),
+        }
+    }
+
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    /// Client builder pointed at this server, retrying quickly and without rate limit.
+    pub fn client_builder(&self) -> ClientBuilder {
+        Client::builder()
+            .base_url(&self.url())
+            .policy(RequestPolicy {
+                initial_backoff: Duration::from_millis(10),
+                max_backoff: Duration::from_millis(100),
+                rate_limit: None,
+                ..RequestPolicy::default()
+            })
+    }
+
+    pub fn client(&self) -> Client {
+        self.client_builder()
+            .build()
+            .expect("failed to build the mock client")
+    }
+
+    /// Serves `body` at `path`, replacing any previous route.
+    pub fn route(&self, path: &str, body: impl Into<Vec<u8>>) {
+        self.state
+            .lock()
+            .unwrap()
+            .routes
+            .insert(path.to_string(), body.into());
+    }
+
+    /// Answers the next `times` requests to `path` with `status` instead of the route.
+    pub fn fail(&self, path: &str, status: u16, times: usize) {
+        self.state.lock().unwrap().failures.insert(
+            path.to_string(),
+            (
+                StatusCode::from_u16(status).expect("invalid status code"),
+                times,
+            ),
+        );
+    }
+
+    /// Number of requests received for `path`.
+    pub fn hits(&self, path: &str) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .hits
+            .get(path)
+            .copied()
+            .unwrap_or(0)
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            shutdown.send(()).ok();
+        }
+    }
+}
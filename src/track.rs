@@ -0,0 +1,412 @@
+use crate::{
+    status::{ApiTrackPoint, ApiTrackResponse},
+    AthleteId, Error,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use protobuf::{CodedInputStream, Message, UnknownFields};
+use serde::{Deserialize, Serialize};
+
+/// Owned, serde-friendly form of an [`ApiTrackResponse`].
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct Track {
+    pub athlete_id: AthleteId,
+    pub points: Vec<TrackPoint>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+#[serde(rename_all = "camelCase")]
+pub struct TrackPoint {
+    /// Unix timestamp in seconds.
+    pub timestamp: f64,
+    pub lat: f32,
+    pub lng: f32,
+    pub altitude: f32,
+    pub altitude_agl: f32,
+    pub status: Option<String>,
+    pub speed: f32,
+    pub vertical_speed: f32,
+}
+
+impl TrackPoint {
+    pub fn time(&self) -> DateTime<Utc> {
+        Utc.timestamp_millis_opt((self.timestamp * 1000.0) as i64)
+            .single()
+            .unwrap_or_default()
+    }
+}
+
+impl From<&ApiTrackPoint> for TrackPoint {
+    fn from(point: &ApiTrackPoint) -> Self {
+        TrackPoint {
+            timestamp: point.get_timestamp(),
+            lat: point.get_lat(),
+            lng: point.get_lng(),
+            altitude: point.get_altitude(),
+            altitude_agl: point.get_altitude_agl(),
+            status: point.has_status().then(|| point.get_status().to_string()),
+            speed: point.get_speed(),
+            vertical_speed: point.get_vertical_speed(),
+        }
+    }
+}
+
+impl From<&TrackPoint> for ApiTrackPoint {
+    fn from(point: &TrackPoint) -> Self {
+        let mut api_point = ApiTrackPoint::new();
+        api_point.set_timestamp(point.timestamp);
+        api_point.set_lat(point.lat);
+        api_point.set_lng(point.lng);
+        api_point.set_altitude(point.altitude);
+        api_point.set_altitude_agl(point.altitude_agl);
+        if let Some(status) = &point.status {
+            api_point.set_status(status.clone());
+        }
+        api_point.set_speed(point.speed);
+        api_point.set_vertical_speed(point.vertical_speed);
+        api_point
+    }
+}
+
+impl Track {
+    /// Checks that the feed's consumers can read the track: every number is
+    /// finite, coordinates are in range and timestamps don't go backwards.
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |index: usize, reason: String| {
+            Err(Error::InvalidTrack(format!("point {}: {}", index, reason)))
+        };
+        let mut previous = f64::NEG_INFINITY;
+        for (index, point) in self.points.iter().enumerate() {
+            let fields = [
+                ("lat", point.lat),
+                ("lng", point.lng),
+                ("altitude", point.altitude),
+                ("altitude_agl", point.altitude_agl),
+                ("speed", point.speed),
+                ("vertical_speed", point.vertical_speed),
+            ];
+            if !point.timestamp.is_finite() {
+                return invalid(index, format!("timestamp is {}", point.timestamp));
+            }
+            if let Some((name, value)) = fields.iter().find(|(_, value)| !value.is_finite()) {
+                return invalid(index, format!("{} is {}", name, value));
+            }
+            if !(-90.0..=90.0).contains(&point.lat) {
+                return invalid(index, format!("latitude {} out of range", point.lat));
+            }
+            if !(-180.0..=180.0).contains(&point.lng) {
+                return invalid(index, format!("longitude {} out of range", point.lng));
+            }
+            if point.timestamp < previous {
+                return invalid(
+                    index,
+                    format!("timestamp {} before {}", point.timestamp, previous),
+                );
+            }
+            previous = point.timestamp;
+        }
+        Ok(())
+    }
+
+    /// The feed message of a valid track, see [`Track::validate`].
+    pub fn to_response(&self) -> Result<ApiTrackResponse, Error> {
+        self.validate()?;
+        let mut response = ApiTrackResponse::new();
+        response.set_athlete_id(self.athlete_id.get());
+        response.set_track_points(self.points.iter().map(ApiTrackPoint::from).collect());
+        Ok(response)
+    }
+
+    /// Serialises a valid track into the `.pbf` feed format, e.g. to write a
+    /// trimmed or simulated track that `test_util::MockServer` can serve.
+    pub fn encode(&self) -> Result<Vec<u8>, Error> {
+        self.to_response()?.encode()
+    }
+}
+
+/// What [`Track::decode_lenient`] had to drop, default or ignore.
+#[derive(Serialize, Debug, Clone, Default, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct DecodeReport {
+    /// The response had no `athlete_id`, the track's is 0.
+    pub missing_athlete_id: bool,
+    /// Points lacking required fields, in feed order.
+    pub points: Vec<PointIssue>,
+    /// Fields not in `track_response.proto`, e.g. ones the feed added since.
+    pub unknown_fields: Vec<UnknownField>,
+}
+
+impl DecodeReport {
+    /// Whether the feed matched the schema, so a strict decode would have
+    /// returned the same track.
+    pub fn is_clean(&self) -> bool {
+        !self.missing_athlete_id && self.points.is_empty() && self.unknown_fields.is_empty()
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct PointIssue {
+    /// Index of the point in the feed, counting dropped points.
+    pub index: usize,
+    pub repair: Repair,
+    /// Names of the required fields the point lacks.
+    pub missing: Vec<&'static str>,
+}
+
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub enum Repair {
+    /// The point has no time or position and was left out.
+    Dropped,
+    /// The missing fields were set to 0.
+    Defaulted,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct UnknownField {
+    /// Feed index of the point the field is on, `None` on the response itself.
+    pub point: Option<usize>,
+    /// Protobuf field number.
+    pub number: u32,
+    /// Number of values the field has.
+    pub values: usize,
+}
+
+fn unknown_fields(fields: &UnknownFields, point: Option<usize>) -> Vec<UnknownField> {
+    let mut unknown: Vec<UnknownField> = fields
+        .iter()
+        .map(|(number, values)| UnknownField {
+            point,
+            number,
+            values: values.iter().count(),
+        })
+        .collect();
+    unknown.sort_by_key(|field| field.number);
+    unknown
+}
+
+impl Track {
+    /// Decodes a `.pbf` track feed that may lack required fields, where
+    /// [`ApiTrackResponse::decode`] would reject it as a whole.
+    ///
+    /// Points without a timestamp, latitude or longitude are dropped, other
+    /// missing fields are set to 0; both are listed in the report. Bytes that
+    /// aren't protobuf at all are still an error.
+    pub fn decode_lenient(bytes: &[u8]) -> Result<(Track, DecodeReport), Error> {
+        let mut response = ApiTrackResponse::new();
+        // Unlike `parse_from_bytes`, merging doesn't check required fields.
+        response.merge_from(&mut CodedInputStream::from_bytes(bytes))?;
+
+        let mut report = DecodeReport {
+            missing_athlete_id: !response.has_athlete_id(),
+            unknown_fields: unknown_fields(response.get_unknown_fields(), None),
+            ..DecodeReport::default()
+        };
+        let mut points = Vec::with_capacity(response.get_track_points().len());
+        for (index, point) in response.get_track_points().iter().enumerate() {
+            report
+                .unknown_fields
+                .extend(unknown_fields(point.get_unknown_fields(), Some(index)));
+            let required = [
+                ("timestamp", point.has_timestamp()),
+                ("lat", point.has_lat()),
+                ("lng", point.has_lng()),
+                ("altitude", point.has_altitude()),
+                ("altitude_agl", point.has_altitude_agl()),
+                ("speed", point.has_speed()),
+                ("vertical_speed", point.has_vertical_speed()),
+            ];
+            let missing: Vec<&'static str> = required
+                .iter()
+                .filter(|(_, present)| !present)
+                .map(|(name, _)| *name)
+                .collect();
+            if missing.is_empty() {
+                points.push(TrackPoint::from(point));
+                continue;
+            }
+            let repair = if point.has_timestamp() && point.has_lat() && point.has_lng() {
+                points.push(TrackPoint::from(point));
+                Repair::Defaulted
+            } else {
+                Repair::Dropped
+            };
+            report.points.push(PointIssue {
+                index,
+                repair,
+                missing,
+            });
+        }
+        let track = Track {
+            athlete_id: response.get_athlete_id().into(),
+            points,
+        };
+        Ok((track, report))
+    }
+}
+
+impl From<&ApiTrackResponse> for Track {
+    fn from(response: &ApiTrackResponse) -> Self {
+        Track {
+            athlete_id: response.get_athlete_id().into(),
+            points: response
+                .get_track_points()
+                .iter()
+                .map(TrackPoint::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<ApiTrackResponse> for Track {
+    fn from(response: ApiTrackResponse) -> Self {
+        Track::from(&response)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{PointIssue, Repair, Track, UnknownField};
+    use crate::{test_util, ApiTrackResponse, AthleteId, Error};
+    use protobuf::{CodedOutputStream, Message};
+
+    const FIXTURES: [&str; 3] = [
+        "/race/athlete/29/track/latest.pbf",
+        "/race/athlete/29/track/reduced.pbf",
+        "/race/athlete/29/track/latest-replay/2023-06-14T16:50:00Z.pbf",
+    ];
+
+    #[test]
+    fn from_response() {
+        let response: ApiTrackResponse = protobuf::Message::parse_from_bytes(
+            test_util::fixture("/race/athlete/29/track/latest.pbf").unwrap(),
+        )
+        .unwrap();
+        let track = Track::from(&response);
+        assert_eq!(track.athlete_id, AthleteId::new(29));
+        assert_eq!(track.points.len(), 60);
+        assert_eq!(track.points[0].status.as_deref(), Some("HIKE"));
+        assert_eq!(
+            track.points[0].time().to_rfc3339(),
+            "2023-06-14T16:00:00+00:00"
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        for path in FIXTURES {
+            let bytes = test_util::fixture(path).unwrap();
+            let response = ApiTrackResponse::decode(bytes).unwrap();
+            let encoded = response.encode().unwrap();
+            assert_eq!(encoded, bytes, "{}", path);
+            assert_eq!(ApiTrackResponse::decode(&encoded).unwrap(), response);
+
+            let track = Track::from(&response);
+            assert_eq!(track.encode().unwrap(), bytes, "{}", path);
+            assert_eq!(
+                Track::from(ApiTrackResponse::decode(&track.encode().unwrap()).unwrap()),
+                track
+            );
+        }
+    }
+
+    #[test]
+    fn invalid() {
+        let track = Track::from(
+            ApiTrackResponse::decode(test_util::fixture(FIXTURES[1]).unwrap()).unwrap(),
+        );
+        let mut nan = track.clone();
+        nan.points[3].altitude_agl = f32::NAN;
+        let mut lat = track.clone();
+        lat.points[0].lat = 91.0;
+        let mut backwards = track.clone();
+        backwards.points.swap(4, 5);
+        for (track, reason) in [
+            (nan, "point 3: altitude_agl is NaN"),
+            (lat, "point 0: latitude 91 out of range"),
+            (backwards, "point 5: timestamp"),
+        ] {
+            match track.encode() {
+                Err(Error::InvalidTrack(message)) => {
+                    assert!(message.starts_with(reason), "{}", message)
+                }
+                other => panic!("{:?}", other),
+            }
+        }
+
+        // Required fields are checked when encoding a response built by hand.
+        let mut response = ApiTrackResponse::new();
+        response.mut_track_points().push(Default::default());
+        assert!(matches!(response.encode(), Err(Error::Protobuf(_))));
+    }
+
+    #[test]
+    fn lenient() {
+        let bytes = test_util::fixture(FIXTURES[1]).unwrap();
+        let (track, report) = Track::decode_lenient(bytes).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(track, Track::from(ApiTrackResponse::decode(bytes).unwrap()));
+
+        let mut response = ApiTrackResponse::decode(bytes).unwrap();
+        response.mut_track_points()[2].clear_altitude_agl();
+        response.mut_track_points()[5].clear_lat();
+        response.mut_track_points()[5].clear_speed();
+        response.mut_track_points()[7]
+            .mut_unknown_fields()
+            .add_varint(9, 1);
+        response
+            .mut_unknown_fields()
+            .add_length_delimited(3, b"new".to_vec());
+        // `write_to_bytes` would refuse the missing required fields.
+        response.compute_size();
+        let mut broken = Vec::new();
+        let mut output = CodedOutputStream::vec(&mut broken);
+        response.write_to_with_cached_sizes(&mut output).unwrap();
+        output.flush().unwrap();
+        drop(output);
+        assert!(ApiTrackResponse::decode(&broken).is_err());
+
+        let (lenient, report) = Track::decode_lenient(&broken).unwrap();
+        assert_eq!(lenient.athlete_id, AthleteId::new(29));
+        assert_eq!(lenient.points.len(), track.points.len() - 1);
+        assert_eq!(lenient.points[2].altitude_agl, 0.0);
+        assert_eq!(lenient.points[5], track.points[6]);
+        assert!(!report.missing_athlete_id);
+        assert_eq!(
+            report.points,
+            [
+                PointIssue {
+                    index: 2,
+                    repair: Repair::Defaulted,
+                    missing: vec!["altitude_agl"],
+                },
+                PointIssue {
+                    index: 5,
+                    repair: Repair::Dropped,
+                    missing: vec!["lat", "speed"],
+                },
+            ]
+        );
+        assert_eq!(
+            report.unknown_fields,
+            [
+                UnknownField {
+                    point: None,
+                    number: 3,
+                    values: 1,
+                },
+                UnknownField {
+                    point: Some(7),
+                    number: 9,
+                    values: 1,
+                },
+            ]
+        );
+
+        assert!(Track::decode_lenient(b"\xff\xff\xff").is_err());
+    }
+}
@@ -0,0 +1,411 @@
+//! Geometry of a [`TurnPoint`]: a cylinder around `lat`/`lng`, or the area
+//! given by its `polygon`.
+//!
+//! The feed doesn't document the polygon encoding. [`Polygon`]'s parser takes
+//! a Google encoded polyline, a JSON array of `[lat, lng]` pairs or a list of
+//! `lat,lng` pairs separated by `;` or whitespace.
+
+use crate::{
+    geo::{LocalFrame, EARTH_RADIUS},
+    LatLng, TurnPoint,
+};
+use serde::{Deserialize, Serialize};
+use std::{fmt, str::FromStr};
+
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+pub struct BoundingBox {
+    pub south: f64,
+    pub west: f64,
+    pub north: f64,
+    pub east: f64,
+}
+
+impl BoundingBox {
+    pub fn contains(&self, point: LatLng) -> bool {
+        (self.south..=self.north).contains(&point.lat)
+            && (self.west..=self.east).contains(&point.lng)
+    }
+}
+
+/// A simple polygon, the last vertex connected to the first.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(try_from = "Vec<LatLng>", into = "Vec<LatLng>")]
+pub struct Polygon {
+    vertices: Vec<LatLng>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsePolygonError(String);
+
+impl fmt::Display for ParsePolygonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid turnpoint polygon: {}", self.0)
+    }
+}
+
+impl std::error::Error for ParsePolygonError {}
+
+impl Polygon {
+    /// Takes at least three vertices; a last vertex repeating the first is dropped.
+    pub fn new(mut vertices: Vec<LatLng>) -> Result<Polygon, ParsePolygonError> {
+        if let Some(invalid) = vertices.iter().find(|vertex| !vertex.is_valid()) {
+            return Err(ParsePolygonError(format!(
+                "vertex {},{} out of range",
+                invalid.lat, invalid.lng
+            )));
+        }
+        if vertices.len() > 1 && vertices.first() == vertices.last() {
+            vertices.pop();
+        }
+        if vertices.len() < 3 {
+            return Err(ParsePolygonError(format!(
+                "{} vertices, at least 3 needed",
+                vertices.len()
+            )));
+        }
+        Ok(Polygon { vertices })
+    }
+
+    pub fn vertices(&self) -> &[LatLng] {
+        &self.vertices
+    }
+
+    /// Edges as pairs of vertices, closing the ring.
+    fn edges(&self) -> impl Iterator<Item = (LatLng, LatLng)> + '_ {
+        self.vertices
+            .iter()
+            .copied()
+            .zip(self.vertices.iter().copied().cycle().skip(1))
+    }
+
+    /// Whether the point is inside, by ray casting in degrees. Doesn't handle
+    /// polygons across the antimeridian or around a pole.
+    pub fn contains(&self, point: LatLng) -> bool {
+        self.edges()
+            .filter(|(a, b)| {
+                (a.lat > point.lat) != (b.lat > point.lat)
+                    && point.lng < (b.lng - a.lng) * (point.lat - a.lat) / (b.lat - a.lat) + a.lng
+            })
+            .count()
+            % 2
+            == 1
+    }
+
+    /// Distance in metres from the point to the nearest edge, inside or out.
+    pub fn distance_to_edge(&self, point: LatLng) -> f64 {
+        let frame = LocalFrame::new(point, 0.0);
+        let offset = |vertex: LatLng| {
+            let enu = frame.to_enu(vertex, 0.0);
+            (enu.east, enu.north)
+        };
+        self.edges()
+            .map(|(a, b)| {
+                let (ax, ay) = offset(a);
+                let (bx, by) = offset(b);
+                let (dx, dy) = (bx - ax, by - ay);
+                let length = dx * dx + dy * dy;
+                let t = if length == 0.0 {
+                    0.0
+                } else {
+                    (-(ax * dx + ay * dy) / length).clamp(0.0, 1.0)
+                };
+                (ax + t * dx).hypot(ay + t * dy)
+            })
+            .fold(f64::INFINITY, f64::min)
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        self.vertices.iter().fold(
+            BoundingBox {
+                south: f64::INFINITY,
+                west: f64::INFINITY,
+                north: f64::NEG_INFINITY,
+                east: f64::NEG_INFINITY,
+            },
+            |bbox, vertex| BoundingBox {
+                south: bbox.south.min(vertex.lat),
+                west: bbox.west.min(vertex.lng),
+                north: bbox.north.max(vertex.lat),
+                east: bbox.east.max(vertex.lng),
+            },
+        )
+    }
+}
+
+impl TryFrom<Vec<LatLng>> for Polygon {
+    type Error = ParsePolygonError;
+
+    fn try_from(vertices: Vec<LatLng>) -> Result<Self, Self::Error> {
+        Polygon::new(vertices)
+    }
+}
+
+impl From<Polygon> for Vec<LatLng> {
+    fn from(polygon: Polygon) -> Self {
+        polygon.vertices
+    }
+}
+
+/// Decodes Google's encoded polyline format with 5 decimal places.
+fn decode_polyline(encoded: &str) -> Option<Vec<LatLng>> {
+    let mut values = Vec::new();
+    let (mut value, mut shift) = (0i64, 0);
+    for byte in encoded.bytes() {
+        if !(63..127).contains(&byte) || shift > 30 {
+            return None;
+        }
+        let chunk = i64::from(byte - 63);
+        value |= (chunk & 0x1f) << shift;
+        shift += 5;
+        if chunk & 0x20 == 0 {
+            values.push(if value & 1 == 1 {
+                !(value >> 1)
+            } else {
+                value >> 1
+            });
+            (value, shift) = (0, 0);
+        }
+    }
+    if shift != 0 || values.len() % 2 != 0 {
+        return None;
+    }
+    let (mut lat, mut lng) = (0, 0);
+    Some(
+        values
+            .chunks(2)
+            .map(|delta| {
+                lat += delta[0];
+                lng += delta[1];
+                LatLng::new(lat as f64 / 1e5, lng as f64 / 1e5)
+            })
+            .collect(),
+    )
+}
+
+fn parse_pairs(pairs: &str) -> Option<Vec<LatLng>> {
+    let numbers = pairs
+        .split(|c: char| c == ',' || c == ';' || c.is_whitespace())
+        .filter(|number| !number.is_empty())
+        .map(str::parse)
+        .collect::<Result<Vec<f64>, _>>()
+        .ok()?;
+    (numbers.len() % 2 == 0).then(|| {
+        numbers
+            .chunks(2)
+            .map(|pair| LatLng::new(pair[0], pair[1]))
+            .collect()
+    })
+}
+
+impl FromStr for Polygon {
+    type Err = ParsePolygonError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let vertices = if s.starts_with('[') {
+            serde_json::from_str::<Vec<[f64; 2]>>(s).ok().map(|pairs| {
+                pairs
+                    .into_iter()
+                    .map(|[lat, lng]| LatLng::new(lat, lng))
+                    .collect()
+            })
+        } else if s.starts_with(|c: char| c == '-' || c.is_ascii_digit()) {
+            parse_pairs(s)
+        } else {
+            decode_polyline(s)
+        };
+        let vertices = vertices.ok_or_else(|| ParsePolygonError("unknown encoding".to_string()))?;
+        Polygon::new(vertices)
+    }
+}
+
+/// The area an athlete has to reach at a turnpoint.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+#[serde(tag = "type", rename_all = "camelCase")]
+pub enum TurnPointGeometry {
+    Cylinder {
+        center: LatLng,
+        /// In metres, 0 for a point.
+        radius: f64,
+    },
+    Polygon {
+        polygon: Polygon,
+    },
+}
+
+impl TurnPointGeometry {
+    pub fn contains(&self, point: LatLng) -> bool {
+        match self {
+            TurnPointGeometry::Cylinder { center, radius } => {
+                center.haversine_distance(point) <= *radius
+            }
+            TurnPointGeometry::Polygon { polygon } => polygon.contains(point),
+        }
+    }
+
+    /// Distance in metres from the point to the boundary, inside or out.
+    pub fn distance_to_edge(&self, point: LatLng) -> f64 {
+        match self {
+            TurnPointGeometry::Cylinder { center, radius } => {
+                (center.haversine_distance(point) - radius).abs()
+            }
+            TurnPointGeometry::Polygon { polygon } => polygon.distance_to_edge(point),
+        }
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        match self {
+            TurnPointGeometry::Cylinder { center, radius } => {
+                let lat = (radius / EARTH_RADIUS).to_degrees();
+                let (south, north) = (center.lat - lat, center.lat + lat);
+                let lng = lat / center.lat.to_radians().cos();
+                // A cylinder around a pole, or so close to one that the
+                // longitude span is infinite or NaN, covers every longitude.
+                let (west, east) =
+                    if south <= -90.0 || north >= 90.0 || lng.is_nan() || lng >= 180.0 {
+                        (-180.0, 180.0)
+                    } else {
+                        (center.lng - lng, center.lng + lng)
+                    };
+                BoundingBox {
+                    south: south.max(-90.0),
+                    west,
+                    north: north.min(90.0),
+                    east,
+                }
+            }
+            TurnPointGeometry::Polygon { polygon } => polygon.bounding_box(),
+        }
+    }
+}
+
+impl TurnPoint {
+    /// The turnpoint's polygon if it has one, else its cylinder.
+    pub fn geometry(&self) -> Result<TurnPointGeometry, ParsePolygonError> {
+        match self.polygon.as_deref().map(str::trim) {
+            Some(polygon) if !polygon.is_empty() => Ok(TurnPointGeometry::Polygon {
+                polygon: polygon.parse()?,
+            }),
+            _ => Ok(TurnPointGeometry::Cylinder {
+                center: LatLng::new(self.lat, self.lng),
+                radius: self.cylinderradius as f64,
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::{LatLng, Polygon, TurnPointGeometry};
+    use crate::{test_util, Overview};
+
+    #[test]
+    fn parse() {
+        let polyline: Polygon = "_p~iF~ps|U_ulLnnqC_mqNvxq`@".parse().unwrap();
+        assert_eq!(
+            polyline.vertices(),
+            [
+                LatLng::new(38.5, -120.2),
+                LatLng::new(40.7, -120.95),
+                LatLng::new(43.252, -126.453),
+            ]
+        );
+        let pairs: Polygon = "38.5,-120.2; 40.7,-120.95 43.252,-126.453 38.5,-120.2"
+            .parse()
+            .unwrap();
+        assert_eq!(pairs, polyline);
+        let json: Polygon = "[[38.5, -120.2], [40.7, -120.95], [43.252, -126.453]]"
+            .parse()
+            .unwrap();
+        assert_eq!(json, polyline);
+        let serialized = serde_json::to_string(&polyline).unwrap();
+        assert_eq!(
+            serde_json::from_str::<Polygon>(&serialized).unwrap(),
+            polyline
+        );
+        assert!(serde_json::from_str::<Polygon>("[]").is_err());
+
+        for invalid in [
+            "46,7 46.1,7 46",
+            "46,7 46.1,7",
+            "[[91, 0], [0, 0], [0, 1]]",
+            "abc{",
+        ] {
+            assert!(invalid.parse::<Polygon>().is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn polygon() {
+        let square = TurnPointGeometry::Polygon {
+            polygon: "46.0,7.0 46.1,7.0 46.1,7.1 46.0,7.1".parse().unwrap(),
+        };
+        let center = LatLng::new(46.05, 7.05);
+        assert!(square.contains(center));
+        assert!(!square.contains(LatLng::new(46.05, 7.15)));
+        // Half of 0.1° of longitude at 46.05° N.
+        assert!((square.distance_to_edge(center) - 3869.7).abs() < 1.0);
+        // 0.05° of latitude, less 1.2 m as the edge bulges north of the parallel.
+        assert!((square.distance_to_edge(LatLng::new(46.15, 7.05)) - 5556.5).abs() < 0.1);
+        let bbox = square.bounding_box();
+        assert_eq!(
+            (bbox.south, bbox.west, bbox.north, bbox.east),
+            (46.0, 7.0, 46.1, 7.1)
+        );
+    }
+
+    #[test]
+    fn fixture_polygon() {
+        let overview: Overview =
+            serde_json::from_slice(test_util::fixture(test_util::OVERVIEW_PATH).unwrap()).unwrap();
+        let titlis = overview.turnpoints[2].geometry().unwrap();
+        let TurnPointGeometry::Polygon { polygon } = &titlis else {
+            panic!("expected a polygon, got {:?}", titlis);
+        };
+        assert_eq!(polygon.vertices().len(), 4);
+        // The cylinder centre is inside, the 400 m radius doesn't apply.
+        let center = LatLng::new(46.77213, 8.43806);
+        assert!(titlis.contains(center));
+        assert!(titlis.contains(LatLng::new(46.78, 8.446)));
+        assert!(!titlis.contains(LatLng::new(46.785, 8.438)));
+        let bbox = titlis.bounding_box();
+        assert_eq!(
+            (bbox.south, bbox.west, bbox.north, bbox.east),
+            (46.762, 8.428, 46.782, 8.448)
+        );
+    }
+
+    #[test]
+    fn cylinder() {
+        let overview: Overview =
+            serde_json::from_slice(test_util::fixture(test_util::OVERVIEW_PATH).unwrap()).unwrap();
+        let geometry = overview.turnpoints[1].geometry().unwrap();
+        let center = LatLng::new(47.80453, 13.11244);
+        assert_eq!(
+            geometry,
+            TurnPointGeometry::Cylinder {
+                center,
+                radius: 400.0
+            }
+        );
+        assert!(geometry.contains(center));
+        assert!((geometry.distance_to_edge(center) - 400.0).abs() < 1e-9);
+        // 0.005° north is 556 m away.
+        let north = LatLng::new(47.80953, 13.11244);
+        assert!(!geometry.contains(north));
+        assert!((geometry.distance_to_edge(north) - 156.0).abs() < 1.0);
+        let bbox = geometry.bounding_box();
+        assert!(bbox.contains(center) && !bbox.contains(north));
+        assert!((bbox.north - 47.80813).abs() < 1e-5);
+
+        for lat in [90.0, 89.9999, -90.0] {
+            let pole = TurnPointGeometry::Cylinder {
+                center: LatLng::new(lat, 13.0),
+                radius: 400.0,
+            };
+            let bbox = pole.bounding_box();
+            assert_eq!((bbox.west, bbox.east), (-180.0, 180.0), "{}", lat);
+            assert!(bbox.south >= -90.0 && bbox.north <= 90.0, "{}", lat);
+        }
+    }
+}
@@ -0,0 +1,69 @@
+//! Bindings for the browser, to decode and analyse tracks client side.
+//!
+//! Build with `wasm-pack build --target web --features wasm`.
+//! Values are handed to JavaScript as plain objects in the same shape as the
+//! JSON feeds, tracks as [`Track`]. Fetching needs the feeds to allow the
+//! page's origin; otherwise fetch the `.pbf` files some other way and decode
+//! them with `decodeTrack`.
+
+use crate::{ApiTrackResponse, AthleteId, Client, Overview, RaceStatus, RaceStatusReplay, Track};
+use chrono::{DateTime, Local, NaiveDate, NaiveTime, TimeZone, Utc};
+use serde::Serialize;
+use wasm_bindgen::prelude::*;
+
+fn to_js<T: Serialize>(value: &T) -> Result<JsValue, JsError> {
+    Ok(value.serialize(&serde_wasm_bindgen::Serializer::json_compatible())?)
+}
+
+#[wasm_bindgen]
+pub async fn overview() -> Result<JsValue, JsError> {
+    to_js(&Overview::request_with(Client::shared()).await?)
+}
+
+#[wasm_bindgen(js_name = raceStatus)]
+pub async fn race_status() -> Result<JsValue, JsError> {
+    to_js(&RaceStatus::request_with(Client::shared()).await?)
+}
+
+/// Every race status snapshot of `date`, as `YYYY-MM-DD`.
+#[wasm_bindgen(js_name = raceStatusReplay)]
+pub async fn race_status_replay(date: String) -> Result<JsValue, JsError> {
+    let date: NaiveDate = date.parse()?;
+    let day = Local
+        .from_local_datetime(&date.and_time(NaiveTime::MIN))
+        .earliest()
+        .ok_or_else(|| JsError::new("invalid date"))?;
+    to_js(&RaceStatusReplay::request_with(Client::shared(), day).await?)
+}
+
+#[wasm_bindgen]
+pub async fn track(athlete_id: String) -> Result<JsValue, JsError> {
+    let athlete_id: AthleteId = athlete_id.parse()?;
+    to_js(&Track::from(
+        ApiTrackResponse::request_with(Client::shared(), athlete_id).await?,
+    ))
+}
+
+#[wasm_bindgen(js_name = trackReduced)]
+pub async fn track_reduced(athlete_id: String) -> Result<JsValue, JsError> {
+    let athlete_id: AthleteId = athlete_id.parse()?;
+    to_js(&Track::from(
+        ApiTrackResponse::request_reduced_with(Client::shared(), athlete_id).await?,
+    ))
+}
+
+/// The track as it was at `time` (RFC 3339), a multiple of 5 minutes.
+#[wasm_bindgen(js_name = trackReplay)]
+pub async fn track_replay(athlete_id: String, time: String) -> Result<JsValue, JsError> {
+    let athlete_id: AthleteId = athlete_id.parse()?;
+    let time: DateTime<Utc> = time.parse()?;
+    to_js(&Track::from(
+        ApiTrackResponse::request_replay_with(Client::shared(), athlete_id, time).await?,
+    ))
+}
+
+/// Decodes the bytes of a `.pbf` track feed.
+#[wasm_bindgen(js_name = decodeTrack)]
+pub fn decode_track(bytes: &[u8]) -> Result<JsValue, JsError> {
+    to_js(&Track::from(ApiTrackResponse::decode(bytes)?))
+}
@@ -0,0 +1,46 @@
+//! Writes the rust-protobuf code for the track feed next to its `.proto`.
+//!
+//! With `--check` nothing is written; it exits with an error if the checked-in
+//! code differs from what the `.proto` generates, e.g. in CI.
+
+use std::{env, fs, path::Path, process::ExitCode};
+
+const PROTO: &str = "src/status/track_response.proto";
+const GENERATED: &str = "src/status/track_response.rs";
+
+fn generate(out_dir: &Path) {
+    protobuf_codegen_pure::Codegen::new()
+        .out_dir(out_dir)
+        .include(".")
+        .input(PROTO)
+        .run()
+        .expect("failed to generate the protobuf code");
+}
+
+fn main() -> ExitCode {
+    if !Path::new(PROTO).exists() {
+        eprintln!("{} not found, run from the repository root", PROTO);
+        return ExitCode::FAILURE;
+    }
+    if !env::args().any(|arg| arg == "--check") {
+        generate(Path::new("src/status"));
+        println!("wrote {}", GENERATED);
+        return ExitCode::SUCCESS;
+    }
+
+    let out_dir = env::temp_dir().join(format!("xalps-protogen-{}", std::process::id()));
+    fs::create_dir_all(&out_dir).expect("failed to create a temporary directory");
+    generate(&out_dir);
+    let generated = fs::read_to_string(out_dir.join("track_response.rs"));
+    let _ = fs::remove_dir_all(&out_dir);
+    if generated.ok() == fs::read_to_string(GENERATED).ok() {
+        println!("{} is up to date", GENERATED);
+        ExitCode::SUCCESS
+    } else {
+        eprintln!(
+            "{} is out of date, run `cargo run --manifest-path tools/protogen/Cargo.toml`",
+            GENERATED
+        );
+        ExitCode::FAILURE
+    }
+}